@@ -0,0 +1,221 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::panic::Location;
+use std::path::Path;
+
+use resvg::tiny_skia::Pixmap;
+
+use crate::backend::{rasterise, rgba, save_png, Backend, Raster, SvgWriter};
+use crate::display::{DisplayList, Event, Item, Recording};
+use crate::locationerror::LocError;
+
+// How long the finished drawing stays up before the animation starts again, in milliseconds.
+const HOLD: u32 = 2000;
+
+// How long a frame stays up, in milliseconds; the last one is held so the drawing can be seen.
+fn frame_delay(index: usize, frames: usize, delay: u32) -> u32 {
+    if index + 1 == frames {
+        delay.max(HOLD)
+    } else {
+        delay
+    }
+}
+
+fn save_error() -> LocError {
+    LocError::new("couldn't save to image path", *Location::caller())
+}
+
+// Plays the recording back as pictures, stopping at the first one that can't be saved.
+fn each_frame(
+    recording: &Recording,
+    display: &DisplayList,
+    mut save: impl FnMut(usize, &Pixmap) -> Result<(), LocError>,
+) -> Result<(), LocError> {
+    let mut result = Ok(());
+    let mut index = 0;
+    recording.play(&display.palette, |frame| {
+        if result.is_ok() {
+            result = match rasterise(frame, &Raster::new(1.0)) {
+                Some(pixmap) => save(index, &pixmap),
+                None => Err(save_error()),
+            };
+        }
+        index += 1;
+    });
+    result
+}
+
+// Writes an animated GIF that loops forever.
+pub fn save_gif(
+    path: &Path,
+    recording: &Recording,
+    display: &DisplayList,
+    delay: u32,
+) -> Result<(), LocError> {
+    let (Ok(width), Ok(height)) = (u16::try_from(display.width), u16::try_from(display.height))
+    else {
+        return Err(LocError::new(
+            "The image is too big for a gif, which can be at most 65535 pixels across",
+            *Location::caller(),
+        ));
+    };
+    let file = File::create(path).map_err(|_| save_error())?;
+    let mut encoder =
+        gif::Encoder::new(BufWriter::new(file), width, height, &[]).map_err(|_| save_error())?;
+    encoder
+        .set_repeat(gif::Repeat::Infinite)
+        .map_err(|_| save_error())?;
+    let frames = recording.frames.len();
+    each_frame(recording, display, |index, pixmap| {
+        let mut pixels = rgba(pixmap);
+        let mut frame = gif::Frame::from_rgba_speed(width, height, &mut pixels, 10);
+        // GIF counts in hundredths of a second.
+        frame.delay = (frame_delay(index, frames, delay) / 10).min(u16::MAX as u32) as u16;
+        encoder.write_frame(&frame).map_err(|_| save_error())
+    })
+}
+
+// Writes an animated PNG that loops forever.
+pub fn save_apng(
+    path: &Path,
+    recording: &Recording,
+    display: &DisplayList,
+    delay: u32,
+) -> Result<(), LocError> {
+    let file = File::create(path).map_err(|_| save_error())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), display.width, display.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let frames = recording.frames.len();
+    encoder
+        .set_animated(frames.max(1) as u32, 0)
+        .map_err(|_| save_error())?;
+    let mut writer = encoder.write_header().map_err(|_| save_error())?;
+    each_frame(recording, display, |index, pixmap| {
+        let delay = frame_delay(index, frames, delay).min(u16::MAX as u32) as u16;
+        writer
+            .set_frame_delay(delay, 1000)
+            .map_err(|_| save_error())?;
+        writer
+            .write_image_data(&rgba(pixmap))
+            .map_err(|_| save_error())
+    })?;
+    writer.finish().map_err(|_| save_error())
+}
+
+// Writes every frame as its own png, numbered after the image path: turtle-0001.png and on.
+pub fn save_frames(
+    path: &Path,
+    recording: &Recording,
+    display: &DisplayList,
+) -> Result<(), LocError> {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    each_frame(recording, display, |index, pixmap| {
+        let frame = path.with_file_name(format!("{stem}-{:04}.png", index + 1));
+        save_png(&frame, pixmap, None).map_err(|_| save_error())
+    })
+}
+
+// Something on show for part of an animation, from the frame it appears until the one it goes.
+struct Shown {
+    item: Item,
+    appear: usize,
+    disappear: Option<usize>,
+}
+
+/*
+    Writes the animation into an svg, which plays itself with SMIL. Everything drawn is
+    in the file once, hidden until the frame it was drawn in and hidden again when the
+    screen is cleaned, so the file is hardly bigger than the finished drawing. The
+    turtles of each frame only show for that frame.
+*/
+pub fn write_svg(svg: &mut SvgWriter, recording: &Recording, display: &DisplayList, delay: u32) {
+    let frames = recording.frames.len();
+    // Each background colour, with when it appears and goes like the things drawn.
+    let mut backgrounds = vec![(recording.start.background, 0, None)];
+    let mut shown: Vec<Shown> = recording
+        .start
+        .items
+        .iter()
+        .map(|item| Shown {
+            item: item.clone(),
+            appear: 0,
+            disappear: None,
+        })
+        .collect();
+    let mut showing: Vec<usize> = (0..shown.len()).collect();
+
+    let mut played = 0;
+    for (index, frame) in recording.frames.iter().enumerate() {
+        for event in &recording.events[played..frame.end] {
+            match event {
+                Event::Draw(Item::Group(_) | Item::EndGroup) => (),
+                Event::Draw(item) => {
+                    showing.push(shown.len());
+                    shown.push(Shown {
+                        item: item.clone(),
+                        appear: index,
+                        disappear: None,
+                    });
+                }
+                Event::Clean => {
+                    for each in showing.drain(..) {
+                        shown[each].disappear = Some(index);
+                    }
+                }
+                Event::Background(colour) => {
+                    if let Some(last) = backgrounds.last_mut() {
+                        last.2 = Some(index);
+                    }
+                    backgrounds.push((*colour, index, None));
+                }
+            }
+        }
+        played = frame.end;
+        // The last frame's turtles stay once the animation is over.
+        let disappear = (index + 1 < frames).then_some(index + 1);
+        shown.extend(frame.sprites.iter().map(|item| Shown {
+            item: item.clone(),
+            appear: index,
+            disappear,
+        }));
+    }
+
+    let seconds = |frame: usize| frame as f32 * delay as f32 / 1000.0;
+    let timed = |svg: &mut SvgWriter, appear: usize, disappear: Option<usize>| {
+        let timed = appear > 0 || disappear.is_some();
+        if timed {
+            svg.begin_timed(seconds(appear), disappear.map(seconds));
+        }
+        timed
+    };
+
+    for (colour, appear, disappear) in backgrounds {
+        if disappear == Some(appear) {
+            continue;
+        }
+        let timed = timed(svg, appear, disappear);
+        svg.background(display.rgb(Some(colour)));
+        if timed {
+            svg.end_timed();
+        }
+    }
+
+    // Things drawn in the same frame, and gone in the same frame, share a group.
+    let shown: Vec<&Shown> = shown
+        .iter()
+        .filter(|shown| shown.disappear != Some(shown.appear))
+        .collect();
+    for run in shown.chunk_by(|a, b| (a.appear, a.disappear) == (b.appear, b.disappear)) {
+        let timed = timed(svg, run[0].appear, run[0].disappear);
+        for shown in run {
+            display.render_item(&shown.item, svg);
+        }
+        if timed {
+            svg.end_timed();
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::fmt::Write;
+
+use unsvg::get_end_coordinates;
+
+use super::plot::{outline, pixel_size, Strokes};
+use super::{number, Backend};
+use crate::canvas::FillRule;
+use crate::colour::{self, Rgb};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Units {
+    Millimetres,
+    Inches,
+}
+
+impl Units {
+    // How many points, 72 to the inch, make one of the unit.
+    pub fn points(&self) -> f32 {
+        match self {
+            Units::Millimetres => 72.0 / 25.4,
+            Units::Inches => 72.0,
+        }
+    }
+}
+
+// How to drive the machine. Sizes and speeds are all in its units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plotter {
+    pub units: Units,
+    // How far the machine can reach, across and up.
+    pub bed: Option<(f32, f32)>,
+    pub pixel: Option<f32>,
+    // Speed while drawing, per minute.
+    pub feed: f32,
+    // Speed while moving between strokes; rapid moves are used when there isn't one.
+    pub travel_feed: Option<f32>,
+    pub pen_up: String,
+    pub pen_down: String,
+}
+
+/*
+    Writes G-code for pen plotters and laser cutters, see Strokes for what gets drawn.
+    Strokes are drawn one colour at a time, pausing to change pens in between, and in
+    whichever order keeps the pen-up travel short.
+    The image sits at the machine's origin with y going up, so it comes out the right
+    way round on the bed.
+*/
+pub struct GcodeWriter {
+    width: u32,
+    height: u32,
+    plotter: Plotter,
+    // How many machine units one pixel takes up.
+    pixel: f32,
+    strokes: Strokes,
+}
+
+impl GcodeWriter {
+    // None if the image doesn't fit on the bed.
+    pub fn new(width: u32, height: u32, plotter: Plotter) -> Option<Self> {
+        let default = 0.75 / plotter.units.points();
+        let pixel = pixel_size(width, height, plotter.bed, plotter.pixel, default)?;
+        Some(GcodeWriter {
+            width,
+            height,
+            plotter,
+            pixel,
+            strokes: Strokes::new(width, height),
+        })
+    }
+
+    // The finished G-code, with a comment naming the program it came from.
+    pub fn finish(self, source: Option<&str>) -> String {
+        let mut gcode = String::new();
+        if let Some(source) = source {
+            let _ = writeln!(gcode, "; drawn by rslogo from {source}");
+        }
+        gcode.push_str(match self.plotter.units {
+            Units::Millimetres => "G21\n",
+            Units::Inches => "G20\n",
+        });
+        gcode.push_str("G90\n");
+        let _ = writeln!(gcode, "{}", self.plotter.pen_up);
+
+        let colours = self.strokes.by_colour((0.0, self.height as f32));
+        for (index, (colour, strokes)) in colours.iter().enumerate() {
+            if colours.len() > 1 {
+                let _ = writeln!(gcode, "; pen {}", colour::to_hex(*colour));
+                if index > 0 {
+                    gcode.push_str("M0\n");
+                }
+            }
+            for stroke in strokes {
+                let _ = writeln!(gcode, "{}", self.travel(stroke[0]));
+                let _ = writeln!(gcode, "{}", self.plotter.pen_down);
+                for (index, point) in stroke.iter().enumerate().skip(1) {
+                    let feed = if index == 1 {
+                        format!(" F{}", number(self.plotter.feed))
+                    } else {
+                        String::new()
+                    };
+                    let (x, y) = self.machine(*point);
+                    let _ = writeln!(gcode, "G1 X{x} Y{y}{feed}");
+                }
+                let _ = writeln!(gcode, "{}", self.plotter.pen_up);
+            }
+        }
+        let _ = writeln!(gcode, "{}", self.travel((0.0, self.height as f32)));
+        gcode
+    }
+
+    // Where a point of the image is on the machine, written ready for a move.
+    fn machine(&self, point: (f32, f32)) -> (String, String) {
+        (
+            number(point.0 * self.pixel),
+            number((self.height as f32 - point.1) * self.pixel),
+        )
+    }
+
+    // Moves to a point with the pen up.
+    fn travel(&self, point: (f32, f32)) -> String {
+        let (x, y) = self.machine(point);
+        match self.plotter.travel_feed {
+            Some(feed) => format!("G1 X{x} Y{y} F{}", number(feed)),
+            None => format!("G0 X{x} Y{y}"),
+        }
+    }
+}
+
+impl Backend for GcodeWriter {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn line(&mut self, start: (f32, f32), direction: i32, length: f32, colour: Rgb) {
+        let end = get_end_coordinates(start.0, start.1, direction, length);
+        self.strokes.add(start, end, colour);
+    }
+
+    // Machines can move in any direction, so lines between points are kept exactly.
+    fn line_between(&mut self, start: (f32, f32), end: (f32, f32), colour: Rgb) {
+        self.strokes.add(start, end, colour);
+    }
+
+    fn background(&mut self, colour: Rgb) {
+        self.strokes.set_background(colour);
+    }
+
+    fn polygon(&mut self, points: &[(f32, f32)], rule: FillRule, colour: Rgb) {
+        outline(self, points, rule, colour);
+    }
+
+    fn dot(&mut self, centre: (f32, f32), radius: f32, colour: Rgb) {
+        self.arc(centre, (radius, radius), 0.0, 0.0, 360.0, colour);
+    }
+}
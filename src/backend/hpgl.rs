@@ -0,0 +1,104 @@
+use std::fmt::Write;
+
+use unsvg::get_end_coordinates;
+
+use super::plot::{outline, pixel_size, Strokes};
+use super::Backend;
+use crate::canvas::FillRule;
+use crate::colour::Rgb;
+
+// HPGL plotters count in steps of 0.025mm, given here in points.
+const PLOTTER_UNIT: f32 = 0.025 * 72.0 / 25.4;
+
+/*
+    Writes HPGL for plotters, see Strokes for what gets drawn. Each colour's number in
+    the palette picks the pen slot, one higher since SP0 puts the pen away, so Logo's
+    black is pen 1 and the other 15 colours follow it.
+    The image sits at the plotter's origin with y going up, so it comes out the right
+    way round on the paper.
+*/
+pub struct HpglWriter {
+    width: u32,
+    height: u32,
+    palette: Vec<Rgb>,
+    // How many plotter units one pixel takes up.
+    pixel: f32,
+    strokes: Strokes,
+}
+
+impl HpglWriter {
+    // The bed and pixel size are in points. None if the image doesn't fit on the bed.
+    pub fn new(
+        width: u32,
+        height: u32,
+        palette: Vec<Rgb>,
+        bed: Option<(f32, f32)>,
+        pixel: Option<f32>,
+    ) -> Option<Self> {
+        let bed = bed.map(|bed| (bed.0 / PLOTTER_UNIT, bed.1 / PLOTTER_UNIT));
+        let pixel = pixel.map(|pixel| pixel / PLOTTER_UNIT);
+        let pixel = pixel_size(width, height, bed, pixel, 0.75 / PLOTTER_UNIT)?;
+        Some(HpglWriter {
+            width,
+            height,
+            palette,
+            pixel,
+            strokes: Strokes::new(width, height),
+        })
+    }
+
+    // The finished HPGL.
+    pub fn finish(self) -> String {
+        let mut hpgl = "IN;\n".to_string();
+        for (colour, strokes) in self.strokes.by_colour((0.0, self.height as f32)) {
+            let pen = self.palette.iter().position(|rgb| *rgb == colour).unwrap() + 1;
+            let _ = writeln!(hpgl, "SP{pen};");
+            for stroke in strokes {
+                let _ = writeln!(hpgl, "PU{};", self.plotter(stroke[0]));
+                let points: Vec<String> = stroke[1..]
+                    .iter()
+                    .map(|point| self.plotter(*point))
+                    .collect();
+                let _ = writeln!(hpgl, "PD{};", points.join(","));
+            }
+            hpgl.push_str("PU;\n");
+        }
+        hpgl.push_str("PU0,0;\nSP0;\n");
+        hpgl
+    }
+
+    // Where a point of the image is on the plotter, in whole plotter units.
+    fn plotter(&self, point: (f32, f32)) -> String {
+        let x = (point.0 * self.pixel).round() as i32;
+        let y = ((self.height as f32 - point.1) * self.pixel).round() as i32;
+        format!("{x},{y}")
+    }
+}
+
+impl Backend for HpglWriter {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn line(&mut self, start: (f32, f32), direction: i32, length: f32, colour: Rgb) {
+        let end = get_end_coordinates(start.0, start.1, direction, length);
+        self.strokes.add(start, end, colour);
+    }
+
+    // Plotters can move in any direction, so lines between points are kept exactly.
+    fn line_between(&mut self, start: (f32, f32), end: (f32, f32), colour: Rgb) {
+        self.strokes.add(start, end, colour);
+    }
+
+    fn background(&mut self, colour: Rgb) {
+        self.strokes.set_background(colour);
+    }
+
+    fn polygon(&mut self, points: &[(f32, f32)], rule: FillRule, colour: Rgb) {
+        outline(self, points, rule, colour);
+    }
+
+    fn dot(&mut self, centre: (f32, f32), radius: f32, colour: Rgb) {
+        self.arc(centre, (radius, radius), 0.0, 0.0, 360.0, colour);
+    }
+}
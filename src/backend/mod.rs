@@ -0,0 +1,161 @@
+/*
+    Something a display list can be drawn onto. Straight lines are all a backend has to
+    draw itself; everything else has a simple version here built out of lines, which a
+    backend that can do better, like true curves or real text, replaces with its own.
+    Points are in image pixels and angles in degrees clockwise from straight up.
+*/
+mod gcode;
+mod hpgl;
+mod pdf;
+mod plot;
+mod raster;
+mod svg;
+mod terminal;
+mod unsvg_image;
+
+pub use gcode::{GcodeWriter, Plotter, Units};
+pub use hpgl::HpglWriter;
+pub use pdf::{page_size, parse_length, PdfWriter};
+pub use raster::{rasterise, rgba, save_png, Raster};
+pub use svg::SvgWriter;
+pub use terminal::{terminal_art, Charset};
+pub use unsvg_image::UnsvgImage;
+
+use crate::canvas::{arc_points, polygon_spans, FillRule};
+use crate::colour::Rgb;
+use crate::font;
+
+pub trait Backend {
+    // How big the image is, in pixels wide and high.
+    fn size(&self) -> (u32, u32);
+
+    fn line(&mut self, start: (f32, f32), direction: i32, length: f32, colour: Rgb);
+
+    // Paints the whole image before anything is drawn on it.
+    fn background(&mut self, colour: Rgb) {
+        let (width, height) = self.size();
+        for row in 0..height {
+            self.line((0.0, row as f32 + 0.5), 90, width as f32, colour);
+        }
+    }
+
+    // Joins two points with a line. Lines only go in whole degree directions, so the
+    // direction is rounded; for the short segments this is used for that is well under a pixel.
+    fn line_between(&mut self, start: (f32, f32), end: (f32, f32), colour: Rgb) {
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        let length = dx.hypot(dy);
+        if length == 0.0 {
+            return;
+        }
+        let direction = dx.atan2(-dy).to_degrees().round() as i32;
+        self.line(start, direction, length, colour);
+    }
+
+    fn arc(
+        &mut self,
+        centre: (f32, f32),
+        radii: (f32, f32),
+        rotation: f32,
+        start: f32,
+        sweep: f32,
+        colour: Rgb,
+    ) {
+        let points = arc_points(centre, radii, rotation, start, sweep);
+        for pair in points.windows(2) {
+            self.line_between(pair[0], pair[1], colour);
+        }
+    }
+
+    fn polygon(&mut self, points: &[(f32, f32)], rule: FillRule, colour: Rgb) {
+        let (width, height) = self.size();
+        let rows = polygon_spans(points, rule, width, height);
+        self.region(&rows, colour);
+    }
+
+    // Paints runs of pixels, each one given as (row, start, end).
+    fn region(&mut self, rows: &[(u32, f32, f32)], colour: Rgb) {
+        for &(row, start, end) in rows {
+            self.line((start, row as f32 + 0.5), 90, end - start, colour);
+        }
+    }
+
+    /*
+        Writes text given its font as a name and the height of its capitals. The name is
+        only for backends that have fonts, everything else draws the stroke font.
+    */
+    fn text(
+        &mut self,
+        position: (f32, f32),
+        text: &str,
+        (_, height): (&str, f32),
+        heading: f32,
+        mirrored: bool,
+        colour: Rgb,
+    ) {
+        for stroke in font::text_strokes(position, text, height, heading, mirrored) {
+            for pair in stroke.windows(2) {
+                self.line_between(pair[0], pair[1], colour);
+            }
+        }
+    }
+
+    // Groups only matter to backends that keep things apart, for everything else they do nothing.
+    fn begin_group(&mut self, _name: &str) {}
+
+    fn end_group(&mut self) {}
+
+    fn dot(&mut self, centre: (f32, f32), radius: f32, colour: Rgb) {
+        let points = arc_points(centre, (radius, radius), 0.0, 0.0, 360.0);
+        self.polygon(&points, FillRule::NonZero, colour);
+    }
+}
+
+// Lines that carry on from each other in one colour, gathered up so they can be written as one path.
+pub struct Polyline {
+    pub colour: Rgb,
+    pub points: Vec<(f32, f32)>,
+}
+
+impl Polyline {
+    pub fn new(start: (f32, f32), end: (f32, f32), colour: Rgb) -> Self {
+        Polyline {
+            colour,
+            points: vec![start, end],
+        }
+    }
+
+    // Adds a line onto the end if it carries on from there in the same colour, returning whether it did.
+    pub fn extend(&mut self, start: (f32, f32), end: (f32, f32), colour: Rgb) -> bool {
+        let last = *self.points.last().unwrap();
+        let joins = (last.0 - start.0).abs() < 0.001 && (last.1 - start.1).abs() < 0.001;
+        if !joins || colour != self.colour {
+            return false;
+        }
+        if start == end {
+            return true;
+        }
+
+        // Carrying on in a straight line only moves the end of the last segment.
+        let before = self.points[self.points.len() - 2];
+        let (ax, ay) = (last.0 - before.0, last.1 - before.1);
+        let (bx, by) = (end.0 - start.0, end.1 - start.1);
+        let cross = ax * by - ay * bx;
+        let straight = ax * bx + ay * by > 0.0 && cross.abs() <= 1e-4 * ax.hypot(ay) * bx.hypot(by);
+        if straight {
+            *self.points.last_mut().unwrap() = end;
+        } else {
+            self.points.push(end);
+        }
+        true
+    }
+}
+
+// Writes a number to three decimal places at most, without trailing zeros.
+pub fn number(value: f32) -> String {
+    let text = format!("{value:.3}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => "0".to_string(),
+        _ => text.to_string(),
+    }
+}
@@ -0,0 +1,359 @@
+use std::fmt::Write;
+
+use unsvg::get_end_coordinates;
+
+use super::{number, Backend, Polyline};
+use crate::canvas::FillRule;
+use crate::colour::Rgb;
+
+// PDF measures everything in points, 72 to the inch.
+const POINTS_PER_MM: f32 = 72.0 / 25.4;
+
+// How big a pixel is when nothing says otherwise, the same as a CSS pixel.
+const PIXEL: f32 = 0.75;
+
+// The space left round the drawing when it is fitted to a page.
+const PAGE_MARGIN: f32 = 10.0 * POINTS_PER_MM;
+
+/*
+    Writes a one page vector PDF by hand. The image is drawn in its own pixels and put on
+    the page by a transform, so it either keeps a pixel size given in physical units or is
+    scaled to fit the page, and sits in the middle of it.
+    Lines that carry on from each other join into one path, and arcs and dots are drawn as
+    Bézier curves.
+*/
+pub struct PdfWriter {
+    width: u32,
+    height: u32,
+    // In points.
+    page: (f32, f32),
+    source: Option<String>,
+    content: String,
+    // The polyline being built.
+    pending: Option<Polyline>,
+}
+
+impl PdfWriter {
+    /*
+        Both the page size and the pixel size are in points. Without a page, the page is
+        just big enough for the image; without a pixel size, the image fills the page
+        apart from a margin.
+    */
+    pub fn new(width: u32, height: u32, page: Option<(f32, f32)>, pixel: Option<f32>) -> Self {
+        let (image_width, image_height) = (width.max(1) as f32, height.max(1) as f32);
+        let pixel = match (page, pixel) {
+            (_, Some(pixel)) => pixel,
+            (Some(page), None) => ((page.0 - 2.0 * PAGE_MARGIN) / image_width)
+                .min((page.1 - 2.0 * PAGE_MARGIN) / image_height)
+                .max(0.0),
+            (None, None) => PIXEL,
+        };
+        let page = page.unwrap_or((image_width * pixel, image_height * pixel));
+
+        // Put the image in the middle of the page, flipped so y runs down it like on an image,
+        // and clip off anything drawn past its edges.
+        let corner = (
+            (page.0 - image_width * pixel) / 2.0,
+            (page.1 - image_height * pixel) / 2.0,
+        );
+        let mut content = String::new();
+        let _ = writeln!(
+            content,
+            "{} 0 0 {} {} {} cm",
+            number(pixel),
+            number(-pixel),
+            number(corner.0),
+            number(page.1 - corner.1)
+        );
+        let _ = writeln!(content, "0 0 {width} {height} re W n");
+        content.push_str("1 w\n");
+
+        PdfWriter {
+            width,
+            height,
+            page,
+            source: None,
+            content,
+            pending: None,
+        }
+    }
+
+    // Names the program the drawing came from in the document's information.
+    pub fn set_source(&mut self, source: &str) {
+        self.source = Some(source.to_string());
+    }
+
+    // The finished PDF file.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.flush();
+
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Contents 4 0 R /Resources << >> >>",
+                number(self.page.0),
+                number(self.page.1)
+            ),
+            format!(
+                "<< /Length {} >>\nstream\n{}endstream",
+                self.content.len(),
+                self.content
+            ),
+        ];
+        let mut info = "<< /Creator (rslogo)".to_string();
+        if let Some(source) = &self.source {
+            let _ = write!(info, " /Title {}", text_string(source));
+        }
+        info.push_str(" >>");
+        objects.push(info);
+
+        // Every object's place in the file goes in the cross reference table at the end.
+        let mut pdf = "%PDF-1.4\n".to_string();
+        let mut offsets = Vec::new();
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            let _ = write!(pdf, "{} 0 obj\n{object}\nendobj\n", index + 1);
+        }
+        let xref = pdf.len();
+        let _ = write!(pdf, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(pdf, "{offset:010} 00000 n ");
+        }
+        let _ = write!(
+            pdf,
+            "trailer\n<< /Size {} /Root 1 0 R /Info {} 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            objects.len() + 1,
+            objects.len()
+        );
+        pdf.into_bytes()
+    }
+
+    // Writes out the polyline being built, if there is one.
+    fn flush(&mut self) {
+        let Some(polyline) = self.pending.take() else {
+            return;
+        };
+        let _ = writeln!(self.content, "{} RG", colour(polyline.colour));
+        for (index, &(x, y)) in polyline.points.iter().enumerate() {
+            let operator = if index == 0 { "m" } else { "l" };
+            let _ = writeln!(self.content, "{} {} {operator}", number(x), number(y));
+        }
+        self.content.push_str("S\n");
+    }
+
+    // Adds the path of part of an ellipse, ready to be stroked or filled.
+    fn arc_path(
+        &mut self,
+        centre: (f32, f32),
+        radii: (f32, f32),
+        rotation: f32,
+        start: f32,
+        sweep: f32,
+    ) {
+        let (from, curves) = arc_curves(centre, radii, rotation, start, sweep);
+        let _ = writeln!(self.content, "{} {} m", number(from.0), number(from.1));
+        for [first, second, end] in curves {
+            let _ = writeln!(
+                self.content,
+                "{} {} {} {} {} {} c",
+                number(first.0),
+                number(first.1),
+                number(second.0),
+                number(second.1),
+                number(end.0),
+                number(end.1)
+            );
+        }
+    }
+}
+
+impl Backend for PdfWriter {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn line(&mut self, start: (f32, f32), direction: i32, length: f32, colour: Rgb) {
+        let end = get_end_coordinates(start.0, start.1, direction, length);
+        self.line_between(start, end, colour);
+    }
+
+    // Paths can go in any direction, so lines between points are kept exactly.
+    fn line_between(&mut self, start: (f32, f32), end: (f32, f32), colour: Rgb) {
+        if let Some(polyline) = &mut self.pending {
+            if polyline.extend(start, end, colour) {
+                return;
+            }
+        }
+        self.flush();
+        self.pending = Some(Polyline::new(start, end, colour));
+    }
+
+    fn background(&mut self, rgb: Rgb) {
+        self.flush();
+        let _ = writeln!(
+            self.content,
+            "{} rg\n0 0 {} {} re f",
+            colour(rgb),
+            self.width,
+            self.height
+        );
+    }
+
+    fn arc(
+        &mut self,
+        centre: (f32, f32),
+        radii: (f32, f32),
+        rotation: f32,
+        start: f32,
+        sweep: f32,
+        rgb: Rgb,
+    ) {
+        self.flush();
+        let _ = writeln!(self.content, "{} RG", colour(rgb));
+        self.arc_path(centre, radii, rotation, start, sweep);
+        self.content.push_str("S\n");
+    }
+
+    fn polygon(&mut self, points: &[(f32, f32)], rule: FillRule, rgb: Rgb) {
+        self.flush();
+        let _ = writeln!(self.content, "{} rg", colour(rgb));
+        for (index, &(x, y)) in points.iter().enumerate() {
+            let operator = if index == 0 { "m" } else { "l" };
+            let _ = writeln!(self.content, "{} {} {operator}", number(x), number(y));
+        }
+        self.content.push_str(match rule {
+            FillRule::EvenOdd => "h f*\n",
+            FillRule::NonZero => "h f\n",
+        });
+    }
+
+    fn region(&mut self, rows: &[(u32, f32, f32)], rgb: Rgb) {
+        self.flush();
+        let _ = writeln!(self.content, "{} rg", colour(rgb));
+        for &(row, start, end) in rows {
+            let _ = writeln!(
+                self.content,
+                "{} {row} {} 1 re",
+                number(start),
+                number(end - start)
+            );
+        }
+        self.content.push_str("f\n");
+    }
+
+    fn dot(&mut self, centre: (f32, f32), radius: f32, rgb: Rgb) {
+        self.flush();
+        let _ = writeln!(self.content, "{} rg", colour(rgb));
+        self.arc_path(centre, (radius, radius), 0.0, 0.0, 360.0);
+        self.content.push_str("h f\n");
+    }
+}
+
+// A cubic Bézier curve carrying on from the last point: two control points then where it ends.
+type Curve = [(f32, f32); 3];
+
+/*
+    Splits part of an ellipse into cubic Bézier curves of at most a quarter turn each,
+    giving where it starts and then the two control points and end of each curve.
+    See arc_points for what each of the arguments mean.
+*/
+fn arc_curves(
+    centre: (f32, f32),
+    radii: (f32, f32),
+    rotation: f32,
+    start: f32,
+    sweep: f32,
+) -> ((f32, f32), Vec<Curve>) {
+    let (sin_r, cos_r) = rotation.to_radians().sin_cos();
+    let turn = |(x, y): (f32, f32)| (x * cos_r - y * sin_r, x * sin_r + y * cos_r);
+    let at = |angle: f32| {
+        let (x, y) = turn((radii.0 * angle.sin(), -radii.1 * angle.cos()));
+        (centre.0 + x, centre.1 + y)
+    };
+    // Which way, and how fast, the ellipse is going at an angle.
+    let tangent = |angle: f32| turn((radii.0 * angle.cos(), radii.1 * angle.sin()));
+
+    let pieces = (sweep.abs() / 90.0).ceil().max(1.0) as usize;
+    let step = (sweep / pieces as f32).to_radians();
+    let handle = 4.0 / 3.0 * (step / 4.0).tan();
+    let start = start.to_radians();
+    let curves = (0..pieces)
+        .map(|piece| {
+            let (from, to) = (
+                start + step * piece as f32,
+                start + step * (piece + 1) as f32,
+            );
+            let (a, b) = (at(from), at(to));
+            let (da, db) = (tangent(from), tangent(to));
+            [
+                (a.0 + da.0 * handle, a.1 + da.1 * handle),
+                (b.0 - db.0 * handle, b.1 - db.1 * handle),
+                b,
+            ]
+        })
+        .collect();
+    (at(start), curves)
+}
+
+// A colour as PDF writes it, each channel from 0 to 1.
+fn colour(rgb: Rgb) -> String {
+    let channel = |c: u8| number(c as f32 / 255.0);
+    format!("{} {} {}", channel(rgb.0), channel(rgb.1), channel(rgb.2))
+}
+
+// Text for the document information, as UTF-16 so any file name comes through.
+fn text_string(text: &str) -> String {
+    let mut hex = "<FEFF".to_string();
+    for unit in text.encode_utf16() {
+        let _ = write!(hex, "{unit:04X}");
+    }
+    hex.push('>');
+    hex
+}
+
+// Reads a length with its unit, like 12mm, 1.5in, 2cm, 10pt or 4px, as points.
+pub fn parse_length(word: &str) -> Option<f32> {
+    let split = word.find(|c: char| c.is_ascii_alphabetic())?;
+    let (value, unit) = word.split_at(split);
+    let value = value.parse::<f32>().ok()?;
+    let points = match unit.to_ascii_lowercase().as_str() {
+        "mm" => POINTS_PER_MM,
+        "cm" => 10.0 * POINTS_PER_MM,
+        "in" => 72.0,
+        "pt" => 1.0,
+        "px" => PIXEL,
+        _ => return None,
+    };
+    (value.is_finite() && value > 0.0).then_some(value * points)
+}
+
+/*
+    Reads a page size in points: A3, A4, A5, Letter, Legal, or a width and height like
+    210x297mm. Named pages are turned on their side for landscape drawings.
+*/
+pub fn page_size(word: &str, landscape: bool) -> Option<(f32, f32)> {
+    let portrait = match word.to_ascii_lowercase().as_str() {
+        "a3" => (297.0 * POINTS_PER_MM, 420.0 * POINTS_PER_MM),
+        "a4" => (210.0 * POINTS_PER_MM, 297.0 * POINTS_PER_MM),
+        "a5" => (148.0 * POINTS_PER_MM, 210.0 * POINTS_PER_MM),
+        "letter" => (8.5 * 72.0, 11.0 * 72.0),
+        "legal" => (8.5 * 72.0, 14.0 * 72.0),
+        _ => {
+            // The unit on the height goes for the width too, unless it has its own.
+            let (width, height) = word.split_once(['x', 'X'])?;
+            let unit = height.trim_start_matches(|c: char| !c.is_ascii_alphabetic());
+            let width = if width.ends_with(|c: char| c.is_ascii_alphabetic()) {
+                parse_length(width)?
+            } else {
+                parse_length(&format!("{width}{unit}"))?
+            };
+            return Some((width, parse_length(height)?));
+        }
+    };
+    Some(if landscape {
+        (portrait.1, portrait.0)
+    } else {
+        portrait
+    })
+}
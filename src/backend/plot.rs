@@ -0,0 +1,150 @@
+use super::Backend;
+use crate::canvas::{clip, FillRule};
+use crate::colour::Rgb;
+
+// A run of joined points, drawn without lifting the pen.
+pub type Stroke = Vec<(f32, f32)>;
+
+/*
+    What a machine with a pen has to be told: only where the pen goes down and moves.
+    Lines in the background colour are left out, since the paper is the background, and
+    anything off the edges of the image is cut off so the pen never leaves the bed.
+*/
+pub struct Strokes {
+    width: u32,
+    height: u32,
+    background: Option<Rgb>,
+    strokes: Vec<(Rgb, Stroke)>,
+}
+
+impl Strokes {
+    pub fn new(width: u32, height: u32) -> Self {
+        Strokes {
+            width,
+            height,
+            background: None,
+            strokes: Vec::new(),
+        }
+    }
+
+    pub fn set_background(&mut self, colour: Rgb) {
+        self.background = Some(colour);
+    }
+
+    // Adds a line, joining it onto the last stroke when it carries on from there.
+    pub fn add(&mut self, start: (f32, f32), end: (f32, f32), colour: Rgb) {
+        if Some(colour) == self.background {
+            return;
+        }
+        let Some((start, end)) = clip(start, end, (self.width as f32, self.height as f32)) else {
+            return;
+        };
+        if start == end {
+            return;
+        }
+        if let Some((stroke_colour, points)) = self.strokes.last_mut() {
+            let last = *points.last().unwrap();
+            if *stroke_colour == colour
+                && (last.0 - start.0).abs() < 0.001
+                && (last.1 - start.1).abs() < 0.001
+            {
+                points.push(end);
+                return;
+            }
+        }
+        self.strokes.push((colour, vec![start, end]));
+    }
+
+    /*
+        The strokes one colour at a time, so each pen only goes in once, in the order the
+        colours were first used. Each colour's strokes are in travel order, carrying on
+        from wherever the last colour finished, starting at a point.
+    */
+    pub fn by_colour(&self, from: (f32, f32)) -> Vec<(Rgb, Vec<Stroke>)> {
+        let mut colours: Vec<Rgb> = Vec::new();
+        for (colour, _) in &self.strokes {
+            if !colours.contains(colour) {
+                colours.push(*colour);
+            }
+        }
+        let mut head = from;
+        colours
+            .into_iter()
+            .map(|colour| {
+                let strokes = self
+                    .strokes
+                    .iter()
+                    .filter(|(stroke_colour, _)| *stroke_colour == colour)
+                    .map(|(_, points)| points)
+                    .collect();
+                let ordered = travel_order(strokes, head);
+                if let Some(last) = ordered.last() {
+                    head = *last.last().unwrap();
+                }
+                (colour, ordered)
+            })
+            .collect()
+    }
+}
+
+/*
+    How many machine units one pixel takes up. Without a pixel size the image is scaled to
+    fill the bed, and without either a default is used. None if the image doesn't fit.
+*/
+pub fn pixel_size(
+    width: u32,
+    height: u32,
+    bed: Option<(f32, f32)>,
+    pixel: Option<f32>,
+    default: f32,
+) -> Option<f32> {
+    let (width, height) = (width.max(1) as f32, height.max(1) as f32);
+    let pixel = match (bed, pixel) {
+        (_, Some(pixel)) => pixel,
+        (Some(bed), None) => (bed.0 / width).min(bed.1 / height),
+        (None, None) => default,
+    };
+    match bed {
+        // A little leeway so rounding never stops a drawing fitted to the bed.
+        Some(bed) if width * pixel > bed.0 * 1.0001 || height * pixel > bed.1 * 1.0001 => None,
+        _ => Some(pixel),
+    }
+}
+
+// Plotters draw fills as their outlines, with every line kept exactly where it goes.
+pub fn outline(backend: &mut impl Backend, points: &[(f32, f32)], _rule: FillRule, colour: Rgb) {
+    for (index, &point) in points.iter().enumerate() {
+        backend.line_between(point, points[(index + 1) % points.len()], colour);
+    }
+}
+
+/*
+    Picks an order to draw strokes in that keeps the travel between them short, by always
+    going to whichever stroke has an end closest to the pen next. Strokes are drawn
+    backwards when their far end is the closer one.
+*/
+fn travel_order(mut strokes: Vec<&Stroke>, from: (f32, f32)) -> Vec<Stroke> {
+    let distance = |a: (f32, f32), b: (f32, f32)| (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2);
+    let mut ordered = Vec::new();
+    let mut head = from;
+    while !strokes.is_empty() {
+        let mut best = (0, false, f32::INFINITY);
+        for (index, stroke) in strokes.iter().enumerate() {
+            let to_start = distance(head, stroke[0]);
+            let to_end = distance(head, *stroke.last().unwrap());
+            if to_start < best.2 {
+                best = (index, false, to_start);
+            }
+            if to_end < best.2 {
+                best = (index, true, to_end);
+            }
+        }
+        let mut stroke = strokes.swap_remove(best.0).clone();
+        if best.1 {
+            stroke.reverse();
+        }
+        head = *stroke.last().unwrap();
+        ordered.push(stroke);
+    }
+    ordered
+}
@@ -0,0 +1,115 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{self, TreeParsing};
+
+use super::SvgWriter;
+use crate::display::DisplayList;
+
+// How to turn a display list into pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Raster {
+    // How many pixels of the picture each pixel of the image becomes, across and down.
+    pub scale: f32,
+    // Each pixel is drawn as this many across and down and then averaged, for smoother edges.
+    pub supersample: u32,
+    // Whether the background is painted in, or left see-through.
+    pub background: bool,
+}
+
+impl Raster {
+    pub fn new(scale: f32) -> Self {
+        Raster {
+            scale,
+            supersample: 1,
+            background: true,
+        }
+    }
+}
+
+/*
+    Draws a display list into pixels for the formats unsvg can't write. It goes through
+    the svg writer and resvg, the same way unsvg draws a png, so frames look like the
+    png of the same drawing would.
+*/
+pub fn rasterise(display: &DisplayList, raster: &Raster) -> Option<Pixmap> {
+    let mut svg = SvgWriter::new(display.width, display.height);
+    // resvg is built without text, so labels go in as strokes.
+    svg.outline_text();
+    if raster.background {
+        display.render(&mut svg);
+    } else {
+        for item in &display.items {
+            display.render_item(item, &mut svg);
+        }
+    }
+    let tree = usvg::Tree::from_str(&svg.finish(), &usvg::Options::default()).ok()?;
+
+    let size = |pixels: u32| (pixels as f32 * raster.scale).ceil().max(1.0) as u32;
+    let (width, height) = (size(display.width), size(display.height));
+    let factor = raster.supersample.max(1);
+    let scale = raster.scale * factor as f32;
+    let mut pixmap = Pixmap::new(width * factor, height * factor)?;
+    resvg::Tree::from_usvg(&tree).render(Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+    if factor == 1 {
+        return Some(pixmap);
+    }
+
+    // Each pixel is the average of the block drawn for it. The colours are still
+    // multiplied by their alpha here, which is what makes a plain average right.
+    let mut shrunk = Pixmap::new(width, height)?;
+    let big = pixmap.data();
+    let small = shrunk.data_mut();
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0u32; 4];
+            for dy in 0..factor {
+                for dx in 0..factor {
+                    let at = (((y * factor + dy) * width * factor + x * factor + dx) * 4) as usize;
+                    for (channel, value) in sum.iter_mut().zip(&big[at..at + 4]) {
+                        *channel += *value as u32;
+                    }
+                }
+            }
+            let at = ((y * width + x) * 4) as usize;
+            for (value, channel) in small[at..at + 4].iter_mut().zip(sum) {
+                *value = ((channel + factor * factor / 2) / (factor * factor)) as u8;
+            }
+        }
+    }
+    Some(shrunk)
+}
+
+// The pixels of a picture as plain red, green, blue and alpha bytes.
+pub fn rgba(pixmap: &Pixmap) -> Vec<u8> {
+    pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let pixel = pixel.demultiply();
+            [pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()]
+        })
+        .collect()
+}
+
+// Writes a picture as a png, saying how many dots to the inch it was drawn at if given.
+pub fn save_png(path: &Path, pixmap: &Pixmap, dpi: Option<f32>) -> Result<(), png::EncodingError> {
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), pixmap.width(), pixmap.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    if let Some(dpi) = dpi {
+        // png counts pixels to the metre.
+        let per_metre = (dpi / 0.0254).round() as u32;
+        encoder.set_pixel_dims(Some(png::PixelDimensions {
+            xppu: per_metre,
+            yppu: per_metre,
+            unit: png::Unit::Meter,
+        }));
+    }
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&rgba(pixmap))?;
+    writer.finish()
+}
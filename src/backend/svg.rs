@@ -0,0 +1,336 @@
+use std::fmt::Write;
+
+use unsvg::get_end_coordinates;
+
+use super::{number, Backend, Polyline};
+use crate::canvas::FillRule;
+use crate::colour::{self, Rgb};
+use crate::font;
+
+// Capitals in most fonts are about this much of the font size.
+const CAP_HEIGHT: f32 = 0.7;
+
+/*
+    Writes svg by hand instead of going through unsvg, so the file can say what the
+    drawing is rather than only how it looks. Lines that carry on from each other in the
+    same colour join into one polyline, arcs and dots are real curves, and each
+    procedure call gets its own group.
+*/
+pub struct SvgWriter {
+    width: u32,
+    height: u32,
+    // The part of the image the file shows, as (x, y, width, height). The whole image if None.
+    view_box: Option<(f32, f32, f32, f32)>,
+    source: Option<String>,
+    // Whether text is drawn in the stroke font rather than written in its own font.
+    outline_text: bool,
+    body: String,
+    depth: usize,
+    // The polyline being built.
+    pending: Option<Polyline>,
+}
+
+impl SvgWriter {
+    pub fn new(width: u32, height: u32) -> Self {
+        SvgWriter {
+            width,
+            height,
+            view_box: None,
+            source: None,
+            outline_text: false,
+            body: String::new(),
+            depth: 1,
+            pending: None,
+        }
+    }
+
+    // Shows only this part of the image, given as the smallest and largest points.
+    pub fn set_view_box(&mut self, low: (f32, f32), high: (f32, f32)) {
+        self.view_box = Some((low.0, low.1, high.0 - low.0, high.1 - low.1));
+    }
+
+    // Names the program the drawing came from in the file's metadata.
+    pub fn set_source(&mut self, source: &str) {
+        self.source = Some(source.to_string());
+    }
+
+    // Draws text in the stroke font, for when what reads the file has no fonts.
+    pub fn outline_text(&mut self) {
+        self.outline_text = true;
+    }
+
+    // The finished svg file.
+    pub fn finish(mut self) -> String {
+        self.flush();
+        while self.depth > 1 {
+            self.end_group();
+        }
+
+        let (x, y, width, height) =
+            self.view_box
+                .unwrap_or((0.0, 0.0, self.width as f32, self.height as f32));
+        let mut svg = format!(
+            "<svg width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\" xmlns=\"http://www.w3.org/2000/svg\">\n",
+            number(width),
+            number(height),
+            number(x),
+            number(y),
+            number(width),
+            number(height),
+        );
+        if let Some(source) = &self.source {
+            svg.push_str("    <metadata>\n");
+            svg.push_str("        <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n");
+            let _ = writeln!(
+                svg,
+                "            <rdf:Description dc:source=\"{}\"/>",
+                escape(source)
+            );
+            svg.push_str("        </rdf:RDF>\n");
+            svg.push_str("    </metadata>\n");
+        }
+        svg.push_str(&self.body);
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /*
+        Starts a group that is hidden until a number of seconds in, and hidden again at
+        the end if there is one, for animations. Needs end_timed after what it holds.
+    */
+    pub fn begin_timed(&mut self, appear: f32, disappear: Option<f32>) {
+        self.element("<g visibility=\"hidden\">");
+        self.depth += 1;
+        self.write(&format!(
+            "<set attributeName=\"visibility\" to=\"visible\" begin=\"{}s\" fill=\"freeze\"/>",
+            number(appear)
+        ));
+        if let Some(disappear) = disappear {
+            self.write(&format!(
+                "<set attributeName=\"visibility\" to=\"hidden\" begin=\"{}s\" fill=\"freeze\"/>",
+                number(disappear)
+            ));
+        }
+    }
+
+    pub fn end_timed(&mut self) {
+        self.end_group();
+    }
+
+    // Adds an element on its own line at the current depth.
+    fn element(&mut self, element: &str) {
+        self.flush();
+        self.write(element);
+    }
+
+    fn write(&mut self, element: &str) {
+        let _ = writeln!(self.body, "{}{element}", "    ".repeat(self.depth));
+    }
+
+    // Writes out the polyline being built, if there is one.
+    fn flush(&mut self) {
+        let Some(polyline) = self.pending.take() else {
+            return;
+        };
+        let points: Vec<String> = polyline
+            .points
+            .iter()
+            .map(|&(x, y)| format!("{},{}", number(x), number(y)))
+            .collect();
+        self.write(&format!(
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\"/>",
+            points.join(" "),
+            colour::to_hex(polyline.colour)
+        ));
+    }
+}
+
+impl Backend for SvgWriter {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn line(&mut self, start: (f32, f32), direction: i32, length: f32, colour: Rgb) {
+        let end = get_end_coordinates(start.0, start.1, direction, length);
+        self.line_between(start, end, colour);
+    }
+
+    // Paths can go in any direction, so lines between points are kept exactly.
+    fn line_between(&mut self, start: (f32, f32), end: (f32, f32), colour: Rgb) {
+        if let Some(polyline) = &mut self.pending {
+            if polyline.extend(start, end, colour) {
+                return;
+            }
+        }
+        self.flush();
+        self.pending = Some(Polyline::new(start, end, colour));
+    }
+
+    fn background(&mut self, colour: Rgb) {
+        let (x, y, width, height) =
+            self.view_box
+                .unwrap_or((0.0, 0.0, self.width as f32, self.height as f32));
+        self.element(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+            number(x),
+            number(y),
+            number(width),
+            number(height),
+            colour::to_hex(colour)
+        ));
+    }
+
+    fn arc(
+        &mut self,
+        centre: (f32, f32),
+        radii: (f32, f32),
+        rotation: f32,
+        start: f32,
+        sweep: f32,
+        colour: Rgb,
+    ) {
+        // A negative radius mirrors the ellipse, which is the same as going round it another way.
+        let (mut start, mut sweep) = (start, sweep);
+        if radii.0 < 0.0 {
+            (start, sweep) = (-start, -sweep);
+        }
+        if radii.1 < 0.0 {
+            (start, sweep) = (180.0 - start, -sweep);
+        }
+        let (rx, ry) = (radii.0.abs(), radii.1.abs());
+        let stroke = colour::to_hex(colour);
+
+        if sweep.abs() >= 360.0 {
+            self.element(&format!(
+                "<ellipse cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\" transform=\"rotate({} {} {})\" fill=\"none\" stroke=\"{stroke}\"/>",
+                number(centre.0),
+                number(centre.1),
+                number(rx),
+                number(ry),
+                number(rotation),
+                number(centre.0),
+                number(centre.1),
+            ));
+            return;
+        }
+
+        let (sin_r, cos_r) = rotation.to_radians().sin_cos();
+        let at = |angle: f32| {
+            let (sin, cos) = angle.to_radians().sin_cos();
+            let (x, y) = (rx * sin, -ry * cos);
+            (
+                centre.0 + x * cos_r - y * sin_r,
+                centre.1 + x * sin_r + y * cos_r,
+            )
+        };
+        let (from, to) = (at(start), at(start + sweep));
+        self.element(&format!(
+            "<path d=\"M {} {} A {} {} {} {} {} {} {}\" fill=\"none\" stroke=\"{stroke}\"/>",
+            number(from.0),
+            number(from.1),
+            number(rx),
+            number(ry),
+            number(rotation),
+            (sweep.abs() > 180.0) as u8,
+            (sweep > 0.0) as u8,
+            number(to.0),
+            number(to.1),
+        ));
+    }
+
+    fn polygon(&mut self, points: &[(f32, f32)], rule: FillRule, colour: Rgb) {
+        let mut path = String::new();
+        for (index, &(x, y)) in points.iter().enumerate() {
+            let command = if index == 0 { "M" } else { " L" };
+            let _ = write!(path, "{command} {} {}", number(x), number(y));
+        }
+        let rule = match rule {
+            FillRule::EvenOdd => "evenodd",
+            FillRule::NonZero => "nonzero",
+        };
+        self.element(&format!(
+            "<path d=\"{path} Z\" fill=\"{}\" fill-rule=\"{rule}\"/>",
+            colour::to_hex(colour)
+        ));
+    }
+
+    // Every run becomes a one pixel high rectangle of the same path.
+    fn region(&mut self, rows: &[(u32, f32, f32)], colour: Rgb) {
+        let runs: Vec<String> = rows
+            .iter()
+            .map(|&(row, start, end)| {
+                format!(
+                    "M {} {row} h {} v 1 h {} Z",
+                    number(start),
+                    number(end - start),
+                    number(start - end)
+                )
+            })
+            .collect();
+        self.element(&format!(
+            "<path d=\"{}\" fill=\"{}\"/>",
+            runs.join(" "),
+            colour::to_hex(colour)
+        ));
+    }
+
+    fn dot(&mut self, centre: (f32, f32), radius: f32, colour: Rgb) {
+        self.element(&format!(
+            "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"/>",
+            number(centre.0),
+            number(centre.1),
+            number(radius),
+            colour::to_hex(colour)
+        ));
+    }
+
+    // Text is written as text in its font, turned and flipped like the stroke font would be.
+    fn text(
+        &mut self,
+        position: (f32, f32),
+        text: &str,
+        (font, height): (&str, f32),
+        heading: f32,
+        mirrored: bool,
+        colour: Rgb,
+    ) {
+        if self.outline_text {
+            for stroke in font::text_strokes(position, text, height, heading, mirrored) {
+                for pair in stroke.windows(2) {
+                    self.line_between(pair[0], pair[1], colour);
+                }
+            }
+            return;
+        }
+        let flip = if mirrored { " scale(-1 1)" } else { "" };
+        self.element(&format!(
+            "<text transform=\"translate({} {}) rotate({}){flip}\" font-family=\"{}\" font-size=\"{}\" fill=\"{}\">{}</text>",
+            number(position.0),
+            number(position.1),
+            number(heading),
+            escape(font),
+            number(height / CAP_HEIGHT),
+            colour::to_hex(colour),
+            escape(text)
+        ));
+    }
+
+    fn begin_group(&mut self, name: &str) {
+        self.element(&format!("<g id=\"{}\">", escape(name)));
+        self.depth += 1;
+    }
+
+    fn end_group(&mut self) {
+        self.flush();
+        self.depth -= 1;
+        self.write("</g>");
+    }
+}
+
+// Makes text safe to put inside an attribute.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,146 @@
+use std::fmt::Write;
+
+use resvg::tiny_skia::Pixmap;
+
+use crate::colour::Rgb;
+
+// How far, out of 255, a pixel has to be from the background to count as drawn on.
+const THRESHOLD: u8 = 48;
+
+// The characters a picture can be printed with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Charset {
+    // Braille patterns, eight dots to a character.
+    Braille,
+    // Quarter blocks, four to a character.
+    Blocks,
+    // Plain ASCII getting darker the more of the character is drawn on.
+    Ascii,
+}
+
+impl Charset {
+    // How many pixels across and down each character stands for.
+    pub fn cell(&self) -> (u32, u32) {
+        match self {
+            Charset::Braille | Charset::Ascii => (2, 4),
+            Charset::Blocks => (2, 2),
+        }
+    }
+
+    // The character for a cell, given which of its pixels are drawn on, row by row.
+    fn character(&self, on: &[bool]) -> char {
+        match self {
+            Charset::Braille => {
+                // Braille numbers its dots down the left column first, with the bottom row last.
+                const DOTS: [u32; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+                let dots = on
+                    .iter()
+                    .zip(DOTS)
+                    .filter(|(on, _)| **on)
+                    .fold(0, |dots, (_, dot)| dots | dot);
+                // A blank pattern is printed as a space, so it can be trimmed off the end.
+                match dots {
+                    0 => ' ',
+                    dots => char::from_u32(0x2800 + dots).unwrap_or(' '),
+                }
+            }
+            Charset::Blocks => {
+                const BLOCKS: [char; 16] = [
+                    ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+                ];
+                let quarters = on
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, on)| **on)
+                    .fold(0, |quarters, (index, _)| quarters | 1 << index);
+                BLOCKS[quarters]
+            }
+            Charset::Ascii => {
+                const RAMP: &[u8] = b" .:-=+*#%@";
+                let count = on.iter().filter(|on| **on).count();
+                RAMP[(count * (RAMP.len() - 1)).div_ceil(on.len())] as char
+            }
+        }
+    }
+}
+
+/*
+    Turns a picture into lines of text for printing in a terminal. A pixel is drawn on
+    when it stands out from the background, and with colour each character is printed
+    in whichever pen colour is closest to what was drawn there, using 24 bit ANSI codes.
+*/
+pub fn terminal_art(
+    pixmap: &Pixmap,
+    background: Rgb,
+    palette: &[Rgb],
+    charset: Charset,
+    colour: bool,
+) -> String {
+    let (cell_width, cell_height) = charset.cell();
+    let pixel = |x: u32, y: u32| -> Option<Rgb> {
+        let pixel = pixmap.pixel(x, y)?.demultiply();
+        let rgb = (pixel.red(), pixel.green(), pixel.blue());
+        let far = rgb.0.abs_diff(background.0) >= THRESHOLD
+            || rgb.1.abs_diff(background.1) >= THRESHOLD
+            || rgb.2.abs_diff(background.2) >= THRESHOLD;
+        far.then_some(rgb)
+    };
+
+    let mut art = String::new();
+    for top in (0..pixmap.height()).step_by(cell_height as usize) {
+        let mut cells = Vec::new();
+        for left in (0..pixmap.width()).step_by(cell_width as usize) {
+            let drawn: Vec<Option<Rgb>> = (0..cell_height)
+                .flat_map(|y| (0..cell_width).map(move |x| (left + x, top + y)))
+                .map(|(x, y)| pixel(x, y))
+                .collect();
+            let on: Vec<bool> = drawn.iter().map(Option::is_some).collect();
+            let character = charset.character(&on);
+            cells.push((character, closest(palette, background, &drawn)));
+        }
+        // Nothing needs printing after the last drawn character.
+        while cells.last().is_some_and(|(character, _)| *character == ' ') {
+            cells.pop();
+        }
+
+        let mut printing = None;
+        for (character, rgb) in cells {
+            if colour && character != ' ' && rgb != printing {
+                if let Some((r, g, b)) = rgb {
+                    let _ = write!(art, "\x1b[38;2;{r};{g};{b}m");
+                }
+                printing = rgb;
+            }
+            art.push(character);
+        }
+        if printing.is_some() {
+            art.push_str("\x1b[0m");
+        }
+        art.push('\n');
+    }
+    // Nor do the empty lines under the drawing.
+    let drawn = art.trim_end_matches('\n').len();
+    art.truncate(drawn);
+    art.push('\n');
+    art
+}
+
+/*
+    The palette colour nearest what was drawn in a cell, if anything was. Edges are
+    blended into the background, so the pixel that stands out most is the truest.
+*/
+fn closest(palette: &[Rgb], background: Rgb, drawn: &[Option<Rgb>]) -> Option<Rgb> {
+    let distance = |a: Rgb, b: Rgb| {
+        let channel = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        channel(a.0, b.0) + channel(a.1, b.1) + channel(a.2, b.2)
+    };
+    let solid = drawn
+        .iter()
+        .flatten()
+        .copied()
+        .max_by_key(|rgb| distance(*rgb, background))?;
+    palette
+        .iter()
+        .copied()
+        .min_by_key(|pen| distance(*pen, solid))
+}
@@ -0,0 +1,72 @@
+use unsvg::{get_end_coordinates, Color, Image};
+
+use super::Backend;
+use crate::canvas::clip;
+use crate::colour::Rgb;
+
+// Draws onto an unsvg image, which can then be saved as an svg or a png.
+pub struct UnsvgImage {
+    pub image: Image,
+    width: u32,
+    height: u32,
+}
+
+impl UnsvgImage {
+    pub fn new(width: u32, height: u32) -> Self {
+        UnsvgImage {
+            image: Image::new(width, height),
+            width,
+            height,
+        }
+    }
+}
+
+impl Backend for UnsvgImage {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn line(&mut self, start: (f32, f32), direction: i32, length: f32, colour: Rgb) {
+        let colour = Color {
+            red: colour.0,
+            green: colour.1,
+            blue: colour.2,
+        };
+        // unsvg can't draw lines that reach far enough off the image, so those are cut
+        // down to a pixel past its edges first. Ones that go off forever can't be drawn at all.
+        let (width, height) = (self.width as f32, self.height as f32);
+        if !start.0.is_finite() || !start.1.is_finite() || !length.is_finite() {
+            return;
+        }
+        let end = get_end_coordinates(start.0, start.1, direction, length);
+        let reach = width + height;
+        let far = |point: (f32, f32)| {
+            !(-reach..=width + reach).contains(&point.0)
+                || !(-reach..=height + reach).contains(&point.1)
+        };
+        let (start, length) = if far(start) || far(end) {
+            let shift = |point: (f32, f32), by: f32| (point.0 + by, point.1 + by);
+            let size = (width + 2.0, height + 2.0);
+            let Some((from, to)) = clip(shift(start, 1.0), shift(end, 1.0), size) else {
+                return;
+            };
+            let length = (to.0 - from.0).hypot(to.1 - from.1);
+            (shift(from, -1.0), length)
+        } else {
+            (start, length)
+        };
+        self.image
+            .draw_simple_line(start.0, start.1, direction, length, colour)
+            .unwrap();
+    }
+
+    // A new image always starts black, anything else has to be painted across every row.
+    fn background(&mut self, colour: Rgb) {
+        if colour == (0, 0, 0) {
+            return;
+        }
+        for row in 0..self.height {
+            self.line((0.0, row as f32 + 0.5), 90, self.width as f32, colour);
+        }
+    }
+}
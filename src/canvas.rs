@@ -0,0 +1,911 @@
+use std::collections::HashMap;
+use std::panic::Location;
+
+use unsvg::{get_end_coordinates, COLORS};
+
+use crate::colour::{self, Rgb};
+use crate::display::{DisplayList, Event, Item, Recording};
+use crate::font;
+use crate::locationerror::LocError;
+use crate::viewport::{Flip, Viewport};
+
+// How a self-intersecting fill polygon decides what counts as "inside".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FillRule {
+    EvenOdd,
+    NonZero,
+}
+
+// How --fit makes the image match the drawing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fit {
+    // Crop or grow the image around the drawing, keeping its size.
+    Size,
+    // Keep the image size, scaling the drawing up or down to fill it.
+    Scale,
+}
+
+// The most pixels across or down an image grown to fit a drawing can be.
+const MAX_FIT_SIZE: f32 = 16384.0;
+
+// What a pen leaves behind on the canvas as it moves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ink {
+    // Paint in a colour.
+    Paint(usize),
+    // Paint over anything already drawn with the background colour.
+    Erase,
+    // Flip every pixel: blank ones take the colour and drawn ones go blank.
+    Reverse(usize),
+}
+
+/*
+    The surface every turtle draws onto. Everything drawn goes into a display list for a
+    backend to draw once the program has finished, and alongside it we keep a shadow
+    raster of the colour index painted into each pixel. This is what lets FILL flood a
+    region the same way a raster Logo would.
+    Points given to the canvas are put through the viewport before anything is drawn;
+    the raster and display list are all in image pixels.
+*/
+pub struct Canvas {
+    pub display: DisplayList,
+    raster: Vec<Option<usize>>,
+    viewport: Viewport,
+    // The smallest and largest image points drawn at, even off the edges of the image.
+    extent: Option<((f32, f32), (f32, f32))>,
+    // How many times each procedure has been called, and the groups of the calls still going.
+    calls: HashMap<String, usize>,
+    open_groups: Vec<String>,
+    // Everything that happens, when making an animation.
+    pub recording: Option<Recording>,
+    // Set while the turtles are drawn for a frame, which leaves the drawing itself alone.
+    overlay: Option<Vec<Item>>,
+    // The source line being run, and the one each item in the display list came from.
+    line: Option<usize>,
+    lines: Vec<Option<usize>>,
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32) -> Self {
+        Canvas {
+            display: DisplayList::new(width, height),
+            raster: vec![None; width as usize * height as usize],
+            viewport: Viewport::new((width as f32 / 2.0, height as f32 / 2.0)),
+            extent: None,
+            calls: HashMap::new(),
+            open_groups: Vec::new(),
+            recording: None,
+            overlay: None,
+            line: None,
+            lines: Vec::new(),
+        }
+    }
+
+    /*
+        Makes an empty canvas the drawing on this one would fit into, with a margin of
+        pixels all round. Running the same program on it draws the same picture, zoomed
+        and shifted to fit. Drawings too big for an image of MAX_FIT_SIZE are an error.
+    */
+    pub fn fitted(&self, fit: Fit, margin: f32) -> Result<Canvas, LocError> {
+        let mut viewport = Viewport::new(self.viewport.centre);
+        let (own_width, own_height) = (self.display.width, self.display.height);
+        let Some((low, high)) = self.extent else {
+            let mut canvas = Canvas::new(own_width, own_height);
+            canvas.viewport = viewport;
+            return Ok(canvas);
+        };
+        let (drawn_width, drawn_height) = (high.0 - low.0, high.1 - low.1);
+        let (width, height, zoom) = match fit {
+            Fit::Size => {
+                let (width, height) = (drawn_width + 2.0 * margin, drawn_height + 2.0 * margin);
+                if width.max(height) > MAX_FIT_SIZE {
+                    return Err(LocError::new(
+                        "The drawing is too big to fit, it would need an image over 16384 pixels across",
+                        *Location::caller(),
+                    ));
+                }
+                (
+                    width.ceil().max(1.0) as u32,
+                    height.ceil().max(1.0) as u32,
+                    1.0,
+                )
+            }
+            Fit::Scale => {
+                let room = |size: u32, drawn: f32| (size as f32 - 2.0 * margin).max(1.0) / drawn;
+                let zoom = room(own_width, drawn_width).min(room(own_height, drawn_height));
+                (
+                    own_width,
+                    own_height,
+                    if zoom.is_finite() { zoom } else { 1.0 },
+                )
+            }
+        };
+
+        // Put the drawing in the middle, which leaves the margin around it.
+        viewport.zoom = zoom;
+        viewport.shift = (
+            (width as f32 - drawn_width * zoom) / 2.0 - low.0 * zoom,
+            (height as f32 - drawn_height * zoom) / 2.0 - low.1 * zoom,
+        );
+        let mut canvas = Canvas::new(width, height);
+        canvas.viewport = viewport;
+        Ok(canvas)
+    }
+
+    pub fn background(&self) -> usize {
+        self.display.background
+    }
+
+    // Changes the background colour behind everything that has been drawn.
+    pub fn set_background(&mut self, colour: usize) {
+        self.display.background = colour;
+        if let Some(recording) = &mut self.recording {
+            recording.record(Event::Background(colour));
+        }
+    }
+
+    // Wipes off everything that has been drawn, leaving only the background.
+    pub fn clean(&mut self) {
+        if let Some(recording) = &mut self.recording {
+            recording.record(Event::Clean);
+        }
+        self.display.items.clear();
+        self.lines.clear();
+        self.extent = None;
+        self.raster.iter_mut().for_each(|pixel| *pixel = None);
+
+        // Procedures still running carry on drawing into their groups.
+        for name in self.open_groups.clone() {
+            self.push(Item::Group(name));
+        }
+    }
+
+    // Starts grouping what gets drawn under a procedure call, numbered by which call it is.
+    pub fn begin_group(&mut self, procedure: &str) {
+        let count = self.calls.entry(procedure.to_string()).or_insert(0);
+        *count += 1;
+        let name = format!("{procedure}-{count}");
+        self.push(Item::Group(name.clone()));
+        self.open_groups.push(name);
+    }
+
+    pub fn end_group(&mut self) {
+        if self.open_groups.pop().is_some() {
+            self.push(Item::EndGroup);
+        }
+    }
+
+    // Starts recording everything drawn from now on, taking a frame every so many items.
+    pub fn record(&mut self, every: usize) {
+        self.recording = Some(Recording::new(every, self.display.clone()));
+    }
+
+    // Whether enough has been drawn for the next frame of an animation.
+    pub fn frame_due(&self) -> bool {
+        self.recording
+            .as_ref()
+            .is_some_and(|recording| recording.due())
+    }
+
+    /*
+        Takes a frame of the animation if anything has changed since the last one. The
+        sprites are drawn onto the frame only, so the turtles showing doesn't change the
+        drawing or what later frames look like.
+    */
+    pub fn take_frame(&mut self, sprites: impl FnOnce(&mut Canvas)) {
+        if !self
+            .recording
+            .as_ref()
+            .is_some_and(|recording| recording.changed())
+        {
+            return;
+        }
+        self.overlay = Some(Vec::new());
+        sprites(self);
+        let sprites = self.overlay.take().unwrap_or_default();
+        if let Some(recording) = &mut self.recording {
+            recording.take_frame(sprites);
+        }
+    }
+
+    // Adds an item to the display list, or to the frame's sprites while they are being drawn.
+    fn push(&mut self, item: Item) {
+        if let Some(overlay) = &mut self.overlay {
+            overlay.push(item);
+            return;
+        }
+        if let Some(recording) = &mut self.recording {
+            recording.record(Event::Draw(item.clone()));
+        }
+        self.display.items.push(item);
+        self.lines.push(self.line);
+    }
+
+    // Marks everything drawn from now on as coming from a line of the program, if any.
+    pub fn set_line(&mut self, line: Option<usize>) {
+        self.line = line;
+    }
+
+    // The source line each item in the display list came from, in the same order.
+    pub fn lines(&self) -> &[Option<usize>] {
+        &self.lines
+    }
+
+    // The smallest and largest image points drawn at, if anything has been drawn.
+    pub fn extent(&self) -> Option<((f32, f32), (f32, f32))> {
+        self.extent
+    }
+
+    // Adds a line to the display list. Lines without a colour are in the background colour.
+    fn stroke(&mut self, start: (f32, f32), end: (f32, f32), colour: Option<usize>) {
+        self.push(Item::Line { start, end, colour });
+        self.extend(start);
+        self.extend(end);
+    }
+
+    // Grows the extent of the drawing to take in a point.
+    fn extend(&mut self, point: (f32, f32)) {
+        if self.overlay.is_some() {
+            return;
+        }
+        let (low, high) = self.extent.unwrap_or((point, point));
+        self.extent = Some((
+            (low.0.min(point.0), low.1.min(point.1)),
+            (high.0.max(point.0), high.1.max(point.1)),
+        ));
+    }
+
+    // The palette number for a colour, adding it to the palette if it is new.
+    pub fn colour(&mut self, rgb: Rgb) -> usize {
+        let palette = &mut self.display.palette;
+        match palette.iter().position(|existing| *existing == rgb) {
+            Some(index) => index,
+            None => {
+                palette.push(rgb);
+                palette.len() - 1
+            }
+        }
+    }
+
+    pub fn rgb(&self, colour: usize) -> Rgb {
+        self.display.palette[colour]
+    }
+
+    // How a colour is written: its number if it is one of the 16 Logo colours, otherwise #rrggbb.
+    pub fn colour_word(&self, colour: usize) -> String {
+        if colour < COLORS.len() {
+            colour.to_string()
+        } else {
+            colour::to_hex(self.rgb(colour))
+        }
+    }
+
+    // Where turtles start, before the viewport: the middle of the image the program was written for.
+    pub fn home(&self) -> (f32, f32) {
+        self.viewport.centre
+    }
+
+    pub fn set_scale(&mut self, scale: f32) {
+        self.viewport.scale = scale;
+    }
+
+    pub fn set_origin(&mut self, origin: (f32, f32)) {
+        self.viewport.origin = origin;
+    }
+
+    pub fn set_rotation(&mut self, rotation: i32) {
+        self.viewport.rotation = rotation;
+    }
+
+    pub fn set_flip(&mut self, flip: Flip) {
+        self.viewport.flip = flip;
+    }
+
+    // Where a point drawn by a turtle lands on the image.
+    pub fn view(&self, point: (f32, f32)) -> (f32, f32) {
+        self.viewport.apply(point)
+    }
+
+    pub fn size(&self) -> (f32, f32) {
+        (self.display.width as f32, self.display.height as f32)
+    }
+
+    /*
+        Checks if a point is on the image the program was written for, the very edges count
+        as on it. That is twice the size of what is around the centre, and --fit doesn't
+        change it, so FENCE stops the turtle in the same places with or without it.
+    */
+    pub fn contains(&self, point: (f32, f32)) -> bool {
+        let (width, height) = (2.0 * self.viewport.centre.0, 2.0 * self.viewport.centre.1);
+        let point = self.viewport.unfitted().apply(point);
+        (0.0..=width).contains(&point.0) && (0.0..=height).contains(&point.1)
+    }
+
+    /*
+        Where a wrapping turtle stays, as its smallest corner and size before the viewport:
+        the image the program was written for, which is twice the size of what is around
+        its centre. Fitting doesn't move the edges, and a turned viewport wraps in the box
+        around the image.
+    */
+    pub fn wrap_area(&self) -> ((f32, f32), (f32, f32)) {
+        let (width, height) = (2.0 * self.viewport.centre.0, 2.0 * self.viewport.centre.1);
+        let corners = [(0.0, 0.0), (width, 0.0), (0.0, height), (width, height)]
+            .map(|corner| self.viewport.unapply(corner));
+        let low = corners
+            .iter()
+            .fold((f32::INFINITY, f32::INFINITY), |low, point| {
+                (low.0.min(point.0), low.1.min(point.1))
+            });
+        let high = corners
+            .iter()
+            .fold((f32::NEG_INFINITY, f32::NEG_INFINITY), |high, point| {
+                (high.0.max(point.0), high.1.max(point.1))
+            });
+        (low, (high.0 - low.0, high.1 - low.1))
+    }
+
+    // Draws a line from a point in a direction and returns where the line ended, before the viewport.
+    pub fn line(
+        &mut self,
+        start: (f32, f32),
+        direction: i32,
+        distance: f32,
+        ink: Ink,
+    ) -> (f32, f32) {
+        let end = get_end_coordinates(start.0, start.1, direction, distance);
+        self.segment(start, end, ink);
+        end
+    }
+
+    // Draws a line between two points.
+    pub fn line_between(&mut self, start: (f32, f32), end: (f32, f32), ink: Ink) {
+        if start == end {
+            return;
+        }
+        self.segment(start, end, ink);
+    }
+
+    /*
+        Draws a line through the viewport. Both ends go through it as they are, rather than
+        as a direction and a length, so lines that aren't at whole degrees still end where
+        they should.
+    */
+    fn segment(&mut self, start: (f32, f32), end: (f32, f32), ink: Ink) {
+        let (start, end) = (self.view(start), self.view(end));
+        match ink {
+            Ink::Paint(colour) => {
+                self.stroke(start, end, Some(colour));
+                self.rasterise_line(start, end, Some(colour));
+            }
+            Ink::Erase => {
+                self.stroke(start, end, None);
+                self.rasterise_line(start, end, None);
+            }
+            Ink::Reverse(colour) => self.reverse_line(start, end, colour),
+        }
+    }
+
+    /*
+        Draws a line in reverse mode. Each pixel the line passes over is flipped on the raster,
+        then the line is split into pieces drawn in either the colour or the background,
+        depending on which way the pixels under each piece flipped.
+    */
+    fn reverse_line(&mut self, start: (f32, f32), end: (f32, f32), colour: usize) {
+        // Nothing off the canvas is drawn, so only the part on it needs going along.
+        let Some((start, end)) = clip(start, end, self.size()) else {
+            return;
+        };
+        let distance = ((end.0 - start.0).powi(2) + (end.1 - start.1).powi(2)).sqrt();
+        let along = |t: f32| {
+            (
+                start.0 + (end.0 - start.0) * t,
+                start.1 + (end.1 - start.1) * t,
+            )
+        };
+
+        // Where each piece starts along the line, and what colour it is.
+        let steps = (distance * 2.0).ceil().max(1.0) as usize;
+        let mut pieces: Vec<(f32, Option<Option<usize>>)> = Vec::new();
+        let mut last_pixel = None;
+        for step in 0..=steps {
+            let t = step as f32 / steps as f32;
+            let pixel = self.pixel_index(along(t));
+            if step > 0 && pixel == last_pixel {
+                continue;
+            }
+            last_pixel = pixel;
+
+            let piece_colour = pixel.map(|pixel| {
+                self.raster[pixel] = match self.raster[pixel] {
+                    Some(_) => None,
+                    None => Some(colour),
+                };
+                self.raster[pixel]
+            });
+            if pieces.last().map(|piece| piece.1) != Some(piece_colour) {
+                // Pieces change halfway between samples.
+                let from = if step == 0 {
+                    0.0
+                } else {
+                    t - 0.5 / steps as f32
+                };
+                pieces.push((from, piece_colour));
+            }
+        }
+
+        for (index, &(from, piece_colour)) in pieces.iter().enumerate() {
+            let to = pieces.get(index + 1).map_or(1.0, |piece| piece.0);
+            if let (Some(piece_colour), true) = (piece_colour, to > from) {
+                self.stroke(along(from), along(to), piece_colour);
+            }
+        }
+    }
+
+    // Draws part of an ellipse, see arc_points for what each of the arguments mean.
+    pub fn arc(
+        &mut self,
+        centre: (f32, f32),
+        radii: (f32, f32),
+        rotation: f32,
+        start: f32,
+        sweep: f32,
+        ink: Ink,
+    ) {
+        let colour = match ink {
+            Ink::Paint(colour) => Some(colour),
+            Ink::Erase => None,
+            // Reversing works pixel by pixel, so the arc has to be drawn as its pieces.
+            Ink::Reverse(_) => {
+                let points = arc_points(centre, radii, rotation, start, sweep);
+                for pair in points.windows(2) {
+                    self.line_between(pair[0], pair[1], ink);
+                }
+                return;
+            }
+        };
+
+        // A mirrored arc goes round the other way.
+        let centre = self.view(centre);
+        let scale = self.viewport.pixels_per_step();
+        let radii = (radii.0 * scale, radii.1 * scale);
+        let rotation = self.viewport.angle(rotation);
+        let sweep = wound(sweep);
+        let (start, sweep) = if self.viewport.mirrors() {
+            (-start, -sweep)
+        } else {
+            (start, sweep)
+        };
+        let points = arc_points(centre, radii, rotation, start, sweep);
+        for pair in points.windows(2) {
+            self.rasterise_line(pair[0], pair[1], colour);
+        }
+        points.iter().for_each(|point| self.extend(*point));
+        self.push(Item::Arc {
+            centre,
+            radii,
+            rotation,
+            start,
+            sweep,
+            colour,
+        });
+    }
+
+    /*
+        Writes text with the built-in stroke font, see font::text_strokes for how it is laid
+        out. Backends that have fonts write it in the named font instead.
+    */
+    pub fn text(
+        &mut self,
+        position: (f32, f32),
+        text: &str,
+        font: &str,
+        height: f32,
+        heading: f32,
+        ink: Ink,
+    ) {
+        let Ink::Paint(colour) = ink else {
+            // Erasing and reversing work pixel by pixel, so the text has to be drawn as its lines.
+            for stroke in font::text_strokes(position, text, height, heading, false) {
+                for pair in stroke.windows(2) {
+                    self.line_between(pair[0], pair[1], ink);
+                }
+            }
+            return;
+        };
+
+        let position = self.view(position);
+        let height = height * self.viewport.pixels_per_step();
+        let heading = self.viewport.angle(heading);
+        let mirrored = self.viewport.mirrors();
+        for stroke in font::text_strokes(position, text, height, heading, mirrored) {
+            for pair in stroke.windows(2) {
+                self.rasterise_line(pair[0], pair[1], Some(colour));
+            }
+            stroke.iter().for_each(|point| self.extend(*point));
+        }
+        self.push(Item::Text {
+            position,
+            text: text.to_string(),
+            font: font.to_string(),
+            height,
+            heading,
+            mirrored,
+            colour,
+        });
+    }
+
+    // Fills the polygon made by the given points, the last point joins back up to the first.
+    pub fn fill_polygon(&mut self, points: &[(f32, f32)], rule: FillRule, colour: usize) {
+        if points.len() < 3 {
+            return;
+        }
+        let points: Vec<(f32, f32)> = points.iter().map(|point| self.view(*point)).collect();
+        // Parts off the image are never painted, but still count towards how big the drawing is.
+        points.iter().for_each(|point| self.extend(*point));
+
+        let (width, height) = (self.display.width, self.display.height);
+        self.paint_rows(&polygon_spans(&points, rule, width, height), colour);
+        self.push(Item::Polygon {
+            points,
+            rule,
+            colour,
+        });
+    }
+
+    // Paints a round dot of the given width, or just the pixel under it when that is too small to fill.
+    pub fn dot(&mut self, centre: (f32, f32), size: f32, ink: Ink) {
+        if size * self.viewport.pixels_per_step() < 2.0 {
+            self.plot(centre, ink);
+            return;
+        }
+        let centre = self.view(centre);
+        let radius = size / 2.0 * self.viewport.pixels_per_step();
+        let points = arc_points(centre, (radius, radius), 0.0, 0.0, 360.0);
+        let (width, height) = (self.display.width, self.display.height);
+        let rows = polygon_spans(&points, FillRule::NonZero, width, height);
+        let Ink::Paint(colour) = ink else {
+            // Erasing and reversing work pixel by pixel, so the dot has to be drawn as its pixels.
+            self.ink_rows(&rows, ink);
+            return;
+        };
+
+        points.iter().for_each(|point| self.extend(*point));
+        self.paint_rows(&rows, colour);
+        self.push(Item::Dot {
+            centre,
+            radius,
+            colour,
+        });
+    }
+
+    // Paints the single pixel a point falls in.
+    pub fn plot(&mut self, point: (f32, f32), ink: Ink) {
+        let point = self.view(point);
+        if self.pixel_index(point).is_some() {
+            let x = point.0.floor();
+            self.ink_rows(&[(point.1.floor() as u32, x, x + 1.0)], ink);
+        }
+    }
+
+    // The colour drawn at a point, None where nothing has been drawn or it is off the canvas.
+    pub fn pixel(&self, point: (f32, f32)) -> Option<usize> {
+        self.pixel_index(self.view(point))
+            .and_then(|pixel| self.raster[pixel])
+    }
+
+    // Flood fills the region of matching colour around a point, like a paint bucket.
+    pub fn flood_fill(&mut self, point: (f32, f32), colour: usize) {
+        let point = self.view(point);
+        let (width, height) = (self.display.width, self.display.height);
+        let (x, y) = (point.0.floor(), point.1.floor());
+        if x < 0.0 || y < 0.0 || x >= width as f32 || y >= height as f32 {
+            return;
+        }
+        let seed = y as usize * width as usize + x as usize;
+        let target = self.raster[seed];
+        if target == Some(colour) {
+            return;
+        }
+
+        // Mark every connected pixel of the target colour.
+        let width = width as usize;
+        let mut filled = vec![false; self.raster.len()];
+        let mut to_visit = vec![seed];
+        while let Some(pixel) = to_visit.pop() {
+            if filled[pixel] || self.raster[pixel] != target {
+                continue;
+            }
+            filled[pixel] = true;
+            if pixel % width > 0 {
+                to_visit.push(pixel - 1);
+            }
+            if pixel % width < width - 1 {
+                to_visit.push(pixel + 1);
+            }
+            if pixel >= width {
+                to_visit.push(pixel - width);
+            }
+            if pixel + width < filled.len() {
+                to_visit.push(pixel + width);
+            }
+        }
+
+        // Gather each run of marked pixels into a single span.
+        let mut rows = Vec::new();
+        for row in 0..height as usize {
+            let mut run_start = None;
+            for column in 0..=width {
+                let inside = column < width && filled[row * width + column];
+                match (inside, run_start) {
+                    (true, None) => run_start = Some(column),
+                    (false, Some(start)) => {
+                        rows.push((row as u32, start as f32, column as f32));
+                        run_start = None;
+                    }
+                    _ => (),
+                }
+            }
+        }
+        self.region(rows, colour);
+    }
+
+    // Paints runs of whole pixels, each given as (row, start, end).
+    fn region(&mut self, rows: Vec<(u32, f32, f32)>, colour: usize) {
+        if rows.is_empty() {
+            return;
+        }
+        for &(row, start, end) in &rows {
+            self.extend((start, row as f32 + 0.5));
+            self.extend((end, row as f32 + 0.5));
+        }
+        self.paint_rows(&rows, colour);
+        self.push(Item::Region {
+            rows,
+            colour: Some(colour),
+        });
+    }
+
+    /*
+        Draws runs of whole pixels with an ink. Erasing and reversing change each pixel on
+        its own, so the runs are split up wherever what the pixels become changes.
+    */
+    fn ink_rows(&mut self, rows: &[(u32, f32, f32)], ink: Ink) {
+        let colour = match ink {
+            Ink::Paint(colour) => return self.region(rows.to_vec(), colour),
+            Ink::Erase => None,
+            Ink::Reverse(colour) => Some(colour),
+        };
+        let width = self.display.width as usize;
+        let mut pieces: Vec<((u32, f32, f32), Option<usize>)> = Vec::new();
+        for &(row, start, end) in rows {
+            // Only pixels whose centre is covered belong to the run.
+            let (first, last) = ((start - 0.5).ceil(), (end - 0.5).floor());
+            if first > last {
+                continue;
+            }
+            for column in first as usize..=last as usize {
+                let pixel = row as usize * width + column;
+                self.raster[pixel] = match (ink, self.raster[pixel]) {
+                    (Ink::Reverse(_), Some(_)) => None,
+                    _ => colour,
+                };
+                let left = column as f32;
+                match pieces.last_mut() {
+                    Some(((last_row, _, end), last_colour))
+                        if *last_row == row
+                            && *end == left
+                            && *last_colour == self.raster[pixel] =>
+                    {
+                        *end += 1.0
+                    }
+                    _ => pieces.push(((row, left, left + 1.0), self.raster[pixel])),
+                }
+            }
+        }
+
+        for pieces in pieces.chunk_by(|a, b| a.1 == b.1) {
+            let rows: Vec<(u32, f32, f32)> = pieces.iter().map(|piece| piece.0).collect();
+            for &(row, start, end) in &rows {
+                self.extend((start, row as f32 + 0.5));
+                self.extend((end, row as f32 + 0.5));
+            }
+            self.push(Item::Region {
+                rows,
+                colour: pieces[0].1,
+            });
+        }
+    }
+
+    // Marks the pixels under runs of a row on the shadow raster.
+    fn paint_rows(&mut self, rows: &[(u32, f32, f32)], colour: usize) {
+        if self.overlay.is_some() {
+            return;
+        }
+        let width = self.display.width as usize;
+        for &(row, start, end) in rows {
+            // Only pixels whose centre is covered belong to the run.
+            let first = (start - 0.5).ceil();
+            let last = (end - 0.5).floor();
+            if first <= last {
+                let row_start = row as usize * width;
+                for column in first as usize..=last as usize {
+                    self.raster[row_start + column] = Some(colour);
+                }
+            }
+        }
+    }
+
+    // Marks the pixels a line passes through on the shadow raster.
+    fn rasterise_line(&mut self, start: (f32, f32), end: (f32, f32), colour: Option<usize>) {
+        if self.overlay.is_some() {
+            return;
+        }
+        // Only the part on the canvas has pixels to set, however long the line is.
+        let Some((start, end)) = clip(start, end, self.size()) else {
+            return;
+        };
+        let length = ((end.0 - start.0).powi(2) + (end.1 - start.1).powi(2)).sqrt();
+        let steps = (length * 2.0).ceil() as usize;
+        for step in 0..=steps {
+            let t = if steps == 0 {
+                0.0
+            } else {
+                step as f32 / steps as f32
+            };
+            self.set_pixel(
+                (
+                    start.0 + (end.0 - start.0) * t,
+                    start.1 + (end.1 - start.1) * t,
+                ),
+                colour,
+            );
+        }
+    }
+
+    fn set_pixel(&mut self, point: (f32, f32), colour: Option<usize>) {
+        if let Some(pixel) = self.pixel_index(point) {
+            self.raster[pixel] = colour;
+        }
+    }
+
+    // Where a point is in the raster, if it is on the canvas at all.
+    fn pixel_index(&self, point: (f32, f32)) -> Option<usize> {
+        let (x, y) = (point.0.floor(), point.1.floor());
+        let (width, height) = self.size();
+        if x >= 0.0 && y >= 0.0 && x < width && y < height {
+            Some(y as usize * width as usize + x as usize)
+        } else {
+            None
+        }
+    }
+}
+
+/*
+    Finds the runs of each row of pixels inside a polygon, as (row, start, end) and cut
+    to the width of the image. A row is inside wherever its middle is.
+*/
+pub fn polygon_spans(
+    points: &[(f32, f32)],
+    rule: FillRule,
+    width: u32,
+    height: u32,
+) -> Vec<(u32, f32, f32)> {
+    let mut spans = Vec::new();
+    for row in 0..height {
+        let y = row as f32 + 0.5;
+
+        // Find where every edge crosses the middle of this row, and which way it was heading.
+        let mut crossings: Vec<(f32, i32)> = Vec::new();
+        for (index, &(x1, y1)) in points.iter().enumerate() {
+            let (x2, y2) = points[(index + 1) % points.len()];
+            if y1 == y2 || y < y1.min(y2) || y >= y1.max(y2) {
+                continue;
+            }
+            let x = x1 + (y - y1) * (x2 - x1) / (y2 - y1);
+            crossings.push((x, if y2 > y1 { 1 } else { -1 }));
+        }
+        crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        // Walk the crossings left to right, keeping every run that is inside the shape.
+        let mut winding = 0;
+        let mut span_start = 0.0;
+        for (index, &(x, direction)) in crossings.iter().enumerate() {
+            let was_inside = match rule {
+                FillRule::EvenOdd => index % 2 == 1,
+                FillRule::NonZero => winding != 0,
+            };
+            winding += direction;
+            let is_inside = match rule {
+                FillRule::EvenOdd => index % 2 == 0,
+                FillRule::NonZero => winding != 0,
+            };
+            if !was_inside && is_inside {
+                span_start = x;
+            } else if was_inside && !is_inside {
+                let (start, end) = (span_start.max(0.0), x.min(width as f32));
+                if end > start {
+                    spans.push((row, start, end));
+                }
+            }
+        }
+    }
+    spans
+}
+
+/*
+    Going round more than once draws nothing new, so a sweep of over a turn is cut down to
+    between one and two turns that end in the same place.
+*/
+pub fn wound(sweep: f32) -> f32 {
+    if sweep.abs() <= 360.0 {
+        return sweep;
+    }
+    sweep.signum() * (360.0 + sweep.abs() % 360.0)
+}
+
+// The fewest degrees between points on an arc, and the most steps one is split into.
+const MIN_ARC_STEP: f32 = 0.01;
+const MAX_ARC_STEPS: f32 = 100_000.0;
+
+/*
+    Splits part of an ellipse into points close enough together that joining them with
+    straight lines stays within a quarter of a pixel of the true curve.
+    Angles are in degrees, clockwise from straight up like headings. The x radius runs
+    across the ellipse and the y radius along it, before it is turned by the rotation.
+*/
+pub fn arc_points(
+    centre: (f32, f32),
+    radii: (f32, f32),
+    rotation: f32,
+    start: f32,
+    sweep: f32,
+) -> Vec<(f32, f32)> {
+    let sweep = wound(sweep);
+    let radius = radii.0.abs().max(radii.1.abs()).max(0.5);
+    // Huge radii round the step down to nothing, so it has a floor and the points a ceiling.
+    let max_step = (2.0 * (1.0 - 0.25 / radius).max(-1.0).acos().to_degrees()).max(MIN_ARC_STEP);
+    let steps = (sweep.abs() / max_step).ceil().clamp(1.0, MAX_ARC_STEPS) as usize;
+
+    let (sin_r, cos_r) = rotation.to_radians().sin_cos();
+    (0..=steps)
+        .map(|step| {
+            let angle = (start + sweep * step as f32 / steps as f32).to_radians();
+            let (x, y) = (radii.0 * angle.sin(), -radii.1 * angle.cos());
+            (
+                centre.0 + x * cos_r - y * sin_r,
+                centre.1 + x * sin_r + y * cos_r,
+            )
+        })
+        .collect()
+}
+
+// Cuts a line down to the part inside the image, if there is any.
+pub fn clip(
+    start: (f32, f32),
+    end: (f32, f32),
+    size: (f32, f32),
+) -> Option<((f32, f32), (f32, f32))> {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let (mut from, mut to) = (0.0_f32, 1.0_f32);
+    // Each edge as how fast the line heads out past it, and how far inside it the line starts.
+    for (outward, inside) in [
+        (-dx, start.0),
+        (dx, size.0 - start.0),
+        (-dy, start.1),
+        (dy, size.1 - start.1),
+    ] {
+        if outward == 0.0 {
+            if inside < 0.0 {
+                return None;
+            }
+        } else if outward < 0.0 {
+            from = from.max(inside / outward);
+        } else {
+            to = to.min(inside / outward);
+        }
+    }
+    (from <= to).then_some((
+        (start.0 + dx * from, start.1 + dy * from),
+        (start.0 + dx * to, start.1 + dy * to),
+    ))
+}
@@ -0,0 +1,94 @@
+/*
+    Colours that aren't in the 16 colour palette. They are written as words like
+    #ff8000 so they can be passed around anywhere a palette number can.
+*/
+pub type Rgb = (u8, u8, u8);
+
+// Reads a colour written as #rrggbb.
+pub fn parse_hex(word: &str) -> Option<Rgb> {
+    let hex = word.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |from: usize| u8::from_str_radix(&hex[from..from + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+pub fn to_hex(rgb: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", rgb.0, rgb.1, rgb.2)
+}
+
+// Hue is in degrees round the colour wheel, saturation and value go from 0 to 1.
+pub fn hsv(hue: f32, saturation: f32, value: f32) -> Rgb {
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let (saturation, value) = (saturation.clamp(0.0, 1.0), value.clamp(0.0, 1.0));
+    let chroma = value * saturation;
+    let second = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (red, green, blue) = match hue as u32 {
+        0 => (chroma, second, 0.0),
+        1 => (second, chroma, 0.0),
+        2 => (0.0, chroma, second),
+        3 => (0.0, second, chroma),
+        4 => (second, 0.0, chroma),
+        _ => (chroma, 0.0, second),
+    };
+    let lightest = value - chroma;
+    let channel = |c: f32| ((c + lightest) * 255.0).round() as u8;
+    (channel(red), channel(green), channel(blue))
+}
+
+// The colour part of the way from one colour to another, 0 being all the first.
+pub fn mix(from: Rgb, to: Rgb, along: f32) -> Rgb {
+    let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * along).round() as u8;
+    (
+        channel(from.0, to.0),
+        channel(from.1, to.1),
+        channel(from.2, to.2),
+    )
+}
+
+// What a gradient is measured against.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Spread {
+    // Goes through every colour over this many steps of line drawn.
+    Along(f32),
+    // Goes through every colour over this many lines drawn.
+    Segment(f32),
+}
+
+/*
+    A pen that changes colour as it draws, blending from each colour to the next and
+    from the last back round to the first, so it can carry on forever.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    pub spread: Spread,
+    stops: Vec<Rgb>,
+    // How far through the gradient the pen has got, in steps or lines.
+    travelled: f32,
+}
+
+impl Gradient {
+    pub fn new(spread: Spread, stops: Vec<Rgb>) -> Self {
+        Gradient {
+            spread,
+            stops,
+            travelled: 0.0,
+        }
+    }
+
+    // The colour a little further on from where the pen has got to.
+    pub fn colour_ahead(&self, ahead: f32) -> Rgb {
+        let period = match self.spread {
+            Spread::Along(period) | Spread::Segment(period) => period,
+        };
+        let phase = ((self.travelled + ahead) / period).rem_euclid(1.0) * self.stops.len() as f32;
+        let from = (phase as usize).min(self.stops.len() - 1);
+        let to = (from + 1) % self.stops.len();
+        mix(self.stops[from], self.stops[to], phase - from as f32)
+    }
+
+    pub fn advance(&mut self, by: f32) {
+        self.travelled += by;
+    }
+}
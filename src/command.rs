@@ -1,35 +1,54 @@
-use crate::Turtle;
+use crate::canvas::Canvas;
+use crate::turtle::{PenMode, Turtle, Turtles};
 use std::cmp::Ordering::{Equal, Greater, Less};
 use std::{collections::HashMap, panic::Location};
 
 use crate::locationerror::LocError;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Command {
     pub arg: Vec<String>,
+    // Where the command is in the source file, counting from 1.
+    pub line: usize,
 }
 
 impl Command {
     // Create a new command struct.
-    pub fn new(command: Vec<String>) -> Self {
-        Command { arg: command }
+    pub fn new(command: Vec<String>, line: usize) -> Self {
+        Command { arg: command, line }
     }
 
     // Checks that the command has the correct num of arguments, useful as it allows use of unwrap() later with certainty.
     pub fn check_command(&self) -> Result<String, LocError> {
-        let operators = ["EQ", "NE", "GT", "LT", "AND", "OR", "+", "-", "*", "/"];
+        let operators = [
+            "EQ", "NE", "GT", "LT", "AND", "OR", "+", "-", "*", "/", "HSV",
+        ];
+        // these queries take a position, so they stand in for more tokens than they resolve to
+        let position_queries = ["TOWARDS", "DISTANCE", "DOTP"];
         // First token will always exist since commands must be non-empty.
         let first_arg: &str = self.first_token();
         match first_arg {
-            "PENUP" | "PENDOWN" | "END" if self.arg.len() != 1 => Err(LocError::new(
-                "Incorrect num arguments",
-                *Location::caller(),
-            )),
-            "TO" if self.arg.len() < 2 => Err(LocError::new(
-                "Incorrect num arguments",
-                *Location::caller(),
-            )),
-            "IF" | "WHILE" => {
+            "PENUP" | "PENDOWN" | "PENPAINT" | "PENERASE" | "PENREVERSE" | "END" | "BEGINFILL"
+            | "ENDFILL" | "FILL" | "HOME" | "WRAP" | "WINDOW" | "FENCE" | "PUSHTURTLE"
+            | "POPTURTLE" | "CLEAN" | "CLEARSCREEN" | "CS" | "SHOWTURTLE" | "ST" | "HIDETURTLE"
+            | "HT" | "STAMP" | "SETPIXEL" | "ORTHOGRAPHIC" | "PERSPECTIVE"
+                if self.arg.len() != 1 =>
+            {
+                Err(LocError::new(
+                    "Incorrect num arguments",
+                    *Location::caller(),
+                ))
+            }
+            "TO" | "LABEL" | "SETPOS" | "NEWTURTLE" | "TELL" | "SETSHAPE" | "SETORIGIN"
+            | "SETPENCOLOR" | "SETFILLCOLOR" | "SETBACKGROUND" | "SETPENGRADIENT"
+                if self.arg.len() < 2 =>
+            {
+                Err(LocError::new(
+                    "Incorrect num arguments",
+                    *Location::caller(),
+                ))
+            }
+            "IF" | "WHILE" | "ASK" => {
                 if self.arg.len() >= 3 {
                     if let Some(last) = self.arg.last() {
                         if last.as_str() == "[" {
@@ -44,14 +63,35 @@ impl Command {
                     ))
                 }
             }
-            "FORWARD" | "BACK" | "LEFT" | "RIGHT" | "SETPENCOLOR" | "TURN" | "SETHEADING"
-            | "SETX" | "SETY"
+            "FORWARD" | "BACK" | "LEFT" | "RIGHT" | "TURN" | "SETHEADING" | "SETX" | "SETY"
+            | "SETFILLRULE" | "CIRCLE" | "SETLABELFONT" | "SETLABELHEIGHT" | "DOT" | "SETZ"
+            | "UPPITCH" | "DOWNPITCH" | "LEFTROLL" | "RIGHTROLL" | "SETSCALE" | "SETROTATION"
+            | "SETFLIP"
                 if self.arg.len() != 2 =>
             {
                 if let Some(v) = self.arg.get(1) {
                     if operators.contains(&v.as_str()) {
                         return Ok(String::from("Expression"));
                     }
+                    if position_queries.contains(&v.as_str()) {
+                        return Ok(String::from("No Expression"));
+                    }
+                }
+                Err(LocError::new(
+                    "Incorrect num arguments",
+                    *Location::caller(),
+                ))
+            }
+            "ARC" | "ARCFORWARD" | "ELLIPSE" | "SETXY" | "SETSCRUNCH" if self.arg.len() != 3 => {
+                if self.arg.iter().any(|v| operators.contains(&v.as_str())) {
+                    return Ok(String::from("Expression"));
+                }
+                if self
+                    .arg
+                    .iter()
+                    .any(|v| position_queries.contains(&v.as_str()))
+                {
+                    return Ok(String::from("No Expression"));
                 }
                 Err(LocError::new(
                     "Incorrect num arguments",
@@ -63,6 +103,9 @@ impl Command {
                     if operators.contains(&v.as_str()) {
                         return Ok(String::from("Expression"));
                     }
+                    if position_queries.contains(&v.as_str()) {
+                        return Ok(String::from("No Expression"));
+                    }
                 }
                 Err(LocError::new(
                     "Incorrect num arguments",
@@ -76,9 +119,11 @@ impl Command {
     // Takes a vec of arguments from a command and formats them by removing the first element and substituting variables for values.
     pub fn get_tokens(
         &self,
-        turtle: &Turtle,
+        turtles: &Turtles,
+        canvas: &Canvas,
         vars: &HashMap<String, String>,
     ) -> Result<Vec<String>, LocError> {
+        let turtle = turtles.active();
         // Check if this command is a comment, if it is "invalid" tokens are allowed
         let mut function_name = false;
         if self.first_token() == "//" {
@@ -109,8 +154,27 @@ impl Command {
                         "XCOR" => Ok(turtle.coords.0.to_string()),
                         "YCOR" => Ok(turtle.coords.1.to_string()),
                         "HEADING" => Ok(turtle.heading.to_string()),
-                        "COLOR" => Ok(turtle.colour.to_string()),
-                        "EQ" | "NE" | "GT" | "LT" | "AND" | "OR" | "+" | "-" | "*" | "/" | "[" => {
+                        "ZCOR" => Ok(turtle.z().to_string()),
+                        "PITCH" => Ok(turtle.pitch().to_string()),
+                        "ROLL" => Ok(turtle.roll().to_string()),
+                        "COLOR" => Ok(canvas.colour_word(turtle.colour)),
+                        "FILLCOLOR" => Ok(canvas.colour_word(turtle.fill_colour)),
+                        "BACKGROUND" => Ok(canvas.colour_word(canvas.background())),
+                        "PIXEL" => Ok(canvas.colour_word(turtle.pixel(canvas))),
+                        "WHO" => Ok(turtles.active_name().to_string()),
+                        "PENDOWNP" if turtle.pen_down() => Ok(String::from("TRUE")),
+                        "PENDOWNP" => Ok(String::from("FALSE")),
+                        "SHOWNP" if turtle.visible() => Ok(String::from("TRUE")),
+                        "SHOWNP" => Ok(String::from("FALSE")),
+                        "PENMODE" => Ok(match turtle.pen_mode() {
+                            PenMode::Paint => String::from("PAINT"),
+                            PenMode::Erase => String::from("ERASE"),
+                            PenMode::Reverse => String::from("REVERSE"),
+                        }),
+                        "EQ" | "NE" | "GT" | "LT" | "AND" | "OR" | "+" | "-" | "*" | "/"
+                        | "HSV" | "[" | "]" => Ok(full_str.to_string()),
+                        // list queries are resolved below, once every token is substituted
+                        "POS" | "SCRUNCH" | "TOWARDS" | "DISTANCE" | "DOTP" | "TURTLES" => {
                             Ok(full_str.to_string())
                         }
                         _ => {
@@ -118,14 +182,14 @@ impl Command {
                                 function_name = false;
                                 return Ok(full_str.to_string());
                             }
-                            return Err(LocError::new("Invalid token given", *Location::caller()));
+                            Err(LocError::new("Invalid token given", *Location::caller()))
                         }
                     }
                 }
             })
             .collect::<Result<_, _>>();
 
-        tokens
+        resolve_list_queries(tokens?, turtles, canvas)
     }
 
     pub fn first_token(&self) -> &str {
@@ -134,6 +198,75 @@ impl Command {
     }
 }
 
+/*
+    Resolves the queries that give back a list, or that take a position after them.
+    Works from the back so a position is always fully resolved before it is used.
+*/
+fn resolve_list_queries(
+    tokens: Vec<String>,
+    turtles: &Turtles,
+    canvas: &Canvas,
+) -> Result<Vec<String>, LocError> {
+    let turtle: &Turtle = turtles.active();
+    // Built up backwards, so the tokens following the current one are at the end.
+    let mut resolved: Vec<String> = Vec::new();
+    for token in tokens.into_iter().rev() {
+        match token.as_str() {
+            "POS" => resolved.extend(to_list(turtle.pos()).into_iter().rev()),
+            "SCRUNCH" => resolved.extend(to_list(turtle.scrunch).into_iter().rev()),
+            "TURTLES" => {
+                resolved.push(String::from("]"));
+                resolved.extend(turtles.names().into_iter().rev());
+                resolved.push(String::from("["));
+            }
+            "TOWARDS" | "DISTANCE" | "DOTP" => {
+                let position = take_position(&mut resolved)?;
+                match token.as_str() {
+                    "TOWARDS" => resolved.push(turtle.towards(position).to_string()),
+                    "DISTANCE" => resolved.push(turtle.distance(position).to_string()),
+                    _ if turtle.dot_at(canvas, position) => resolved.push(String::from("TRUE")),
+                    _ => resolved.push(String::from("FALSE")),
+                }
+            }
+            _ => resolved.push(token),
+        }
+    }
+    resolved.reverse();
+    Ok(resolved)
+}
+
+fn to_list(pair: (f32, f32)) -> Vec<String> {
+    vec![
+        String::from("["),
+        pair.0.to_string(),
+        pair.1.to_string(),
+        String::from("]"),
+    ]
+}
+
+// Takes a position, either "[ x y ]" or just "x y", off the end of a backwards list of tokens.
+fn take_position(resolved: &mut Vec<String>) -> Result<(f32, f32), LocError> {
+    let bracketed = resolved.last().map(|s| s.as_str()) == Some("[");
+    if bracketed {
+        resolved.pop();
+    }
+    let x = resolved.pop();
+    let y = resolved.pop();
+    if bracketed && resolved.pop().as_deref() != Some("]") {
+        return Err(LocError::new(
+            "Missing ] after position",
+            *Location::caller(),
+        ));
+    }
+    match (x.map(|x| x.parse::<f32>()), y.map(|y| y.parse::<f32>())) {
+        (Some(Ok(x)), Some(Ok(y))) => Ok((x, y)),
+        _ => Err(LocError::new(
+            "Position must be two numbers",
+            *Location::caller(),
+        )),
+    }
+}
+
 /*
     Checks that we dont have any loose "TO"'s or "END"'s floating about in the file.
     The num of each must be equal or return an appropriate error message.
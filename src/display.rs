@@ -0,0 +1,255 @@
+use unsvg::COLORS;
+
+use crate::backend::Backend;
+use crate::canvas::FillRule;
+use crate::colour::Rgb;
+
+/*
+    One thing drawn onto the image. Everything is already through the viewport, so
+    points and sizes are in image pixels and angles are degrees clockwise from straight
+    up. Colours are numbers into the palette; those without one are the background
+    colour, whatever it ends up being.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    // A straight line between two points.
+    Line {
+        start: (f32, f32),
+        end: (f32, f32),
+        colour: Option<usize>,
+    },
+    // Part of an ellipse, see arc_points for what each of the fields mean.
+    Arc {
+        centre: (f32, f32),
+        radii: (f32, f32),
+        rotation: f32,
+        start: f32,
+        sweep: f32,
+        colour: Option<usize>,
+    },
+    // A filled polygon, the last point joins back up to the first.
+    Polygon {
+        points: Vec<(f32, f32)>,
+        rule: FillRule,
+        colour: usize,
+    },
+    // Runs of whole pixels as (row, start, end), left by flood fills, single pixels and
+    // dots that aren't painted.
+    Region {
+        rows: Vec<(u32, f32, f32)>,
+        colour: Option<usize>,
+    },
+    // Text in the stroke font, see font::text_strokes, or in the named font where there are fonts.
+    Text {
+        position: (f32, f32),
+        text: String,
+        font: String,
+        height: f32,
+        heading: f32,
+        mirrored: bool,
+        colour: usize,
+    },
+    // A filled circle.
+    Dot {
+        centre: (f32, f32),
+        radius: f32,
+        colour: usize,
+    },
+    // Everything up to the matching EndGroup was drawn by one procedure call, named like Box-3.
+    Group(String),
+    EndGroup,
+}
+
+/*
+    Everything drawn, in the order it was drawn, kept apart from any one kind of image.
+    A backend draws the finished list however suits it, so turtles never need to know
+    what the picture is being saved as.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisplayList {
+    pub width: u32,
+    pub height: u32,
+    pub background: usize,
+    // Starts as the 16 Logo colours and grows as other colours get used.
+    pub palette: Vec<Rgb>,
+    pub items: Vec<Item>,
+}
+
+impl DisplayList {
+    pub fn new(width: u32, height: u32) -> Self {
+        DisplayList {
+            width,
+            height,
+            background: 0,
+            palette: COLORS
+                .iter()
+                .map(|colour| (colour.red, colour.green, colour.blue))
+                .collect(),
+            items: Vec::new(),
+        }
+    }
+
+    // The colour of an item, items without one being the background colour.
+    pub fn rgb(&self, colour: Option<usize>) -> Rgb {
+        self.palette[colour.unwrap_or(self.background)]
+    }
+
+    // Draws the background and then every item onto a backend.
+    pub fn render(&self, backend: &mut dyn Backend) {
+        backend.background(self.rgb(None));
+        for item in &self.items {
+            self.render_item(item, backend);
+        }
+    }
+
+    // Draws one item onto a backend, in this list's colours.
+    pub fn render_item(&self, item: &Item, backend: &mut dyn Backend) {
+        match item {
+            Item::Line { start, end, colour } => {
+                backend.line_between(*start, *end, self.rgb(*colour))
+            }
+            Item::Arc {
+                centre,
+                radii,
+                rotation,
+                start,
+                sweep,
+                colour,
+            } => backend.arc(
+                *centre,
+                *radii,
+                *rotation,
+                *start,
+                *sweep,
+                self.rgb(*colour),
+            ),
+            Item::Polygon {
+                points,
+                rule,
+                colour,
+            } => backend.polygon(points, *rule, self.rgb(Some(*colour))),
+            Item::Region { rows, colour } => backend.region(rows, self.rgb(*colour)),
+            Item::Text {
+                position,
+                text,
+                font,
+                height,
+                heading,
+                mirrored,
+                colour,
+            } => backend.text(
+                *position,
+                text,
+                (font, *height),
+                *heading,
+                *mirrored,
+                self.rgb(Some(*colour)),
+            ),
+            Item::Dot {
+                centre,
+                radius,
+                colour,
+            } => backend.dot(*centre, *radius, self.rgb(Some(*colour))),
+            Item::Group(name) => backend.begin_group(name),
+            Item::EndGroup => backend.end_group(),
+        }
+    }
+}
+
+// Something that changed the display list, kept so the drawing can be played back.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Draw(Item),
+    Clean,
+    Background(usize),
+}
+
+// One frame of an animation: how many events had happened, and the turtles on top at the time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub end: usize,
+    pub sprites: Vec<Item>,
+}
+
+/*
+    A drawing as it happened, for animations. Every change to the display list is kept in
+    order, and a frame is taken each time enough more has been drawn, so any frame can be
+    rebuilt by playing the events back up to where it ends.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    // How many items are drawn between frames.
+    pub every: usize,
+    // What the display list was like when recording started.
+    pub start: DisplayList,
+    pub events: Vec<Event>,
+    pub frames: Vec<Frame>,
+    drawn_since_frame: usize,
+}
+
+impl Recording {
+    pub fn new(every: usize, start: DisplayList) -> Self {
+        Recording {
+            every: every.max(1),
+            start,
+            events: Vec::new(),
+            frames: Vec::new(),
+            drawn_since_frame: 0,
+        }
+    }
+
+    pub fn record(&mut self, event: Event) {
+        // Groups don't draw anything, so they don't count towards a frame.
+        if let Event::Draw(item) = &event {
+            if !matches!(item, Item::Group(_) | Item::EndGroup) {
+                self.drawn_since_frame += 1;
+            }
+        }
+        self.events.push(event);
+    }
+
+    // Whether enough has been drawn for another frame.
+    pub fn due(&self) -> bool {
+        self.drawn_since_frame >= self.every
+    }
+
+    // Whether anything has happened since the last frame was taken.
+    pub fn changed(&self) -> bool {
+        match self.frames.last() {
+            Some(frame) => frame.end < self.events.len(),
+            None => true,
+        }
+    }
+
+    pub fn take_frame(&mut self, sprites: Vec<Item>) {
+        self.frames.push(Frame {
+            end: self.events.len(),
+            sprites,
+        });
+        self.drawn_since_frame = 0;
+    }
+
+    /*
+        Plays the drawing back, handing over the display list as it looked at each frame
+        with that frame's turtles on top. The palette is the finished one, which only ever
+        grew, so every colour means the same thing in every frame.
+    */
+    pub fn play(&self, palette: &[Rgb], mut frame: impl FnMut(&DisplayList)) {
+        let mut list = self.start.clone();
+        list.palette = palette.to_vec();
+        let mut played = 0;
+        for each in &self.frames {
+            for event in &self.events[played..each.end] {
+                match event {
+                    Event::Draw(item) => list.items.push(item.clone()),
+                    Event::Clean => list.items.clear(),
+                    Event::Background(colour) => list.background = *colour,
+                }
+            }
+            played = each.end;
+            let mut shown = list.clone();
+            shown.items.extend(each.sprites.iter().cloned());
+            frame(&shown);
+        }
+    }
+}
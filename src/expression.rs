@@ -1,3 +1,4 @@
+use crate::colour;
 use crate::locationerror::LocError;
 use std::panic::Location;
 enum Operation {
@@ -113,6 +114,23 @@ impl Operation {
         }
     }
 
+    // HSV is the only operation on 3 arguments, giving back a colour word like #ff8000
+    fn hsv(hue: String, saturation: String, value: String) -> Result<String, LocError> {
+        match (
+            hue.parse::<f32>(),
+            saturation.parse::<f32>(),
+            value.parse::<f32>(),
+        ) {
+            (Ok(hue), Ok(saturation), Ok(value)) => {
+                Ok(colour::to_hex(colour::hsv(hue, saturation, value)))
+            }
+            _ => Err(LocError::new(
+                "HSV expression wasnt given 3 numbers",
+                *Location::caller(),
+            )),
+        }
+    }
+
     fn to_nums(operand1: String, operand2: String) -> Result<(f32, f32), LocError> {
         let op1_conv = operand1.parse::<f32>();
         let op2_conv = operand2.parse::<f32>();
@@ -139,21 +157,23 @@ impl Operation {
 
 // takes the list of tokens, finds any valid polish expressions and evaluates them
 pub fn evaluate_polish(mut tokens: Vec<String>) -> Result<Vec<String>, LocError> {
-    let operators = ["EQ", "NE", "GT", "LT", "AND", "OR", "+", "-", "*", "/"];
+    let operators = [
+        "EQ", "NE", "GT", "LT", "AND", "OR", "+", "-", "*", "/", "HSV",
+    ];
 
-    // find any valid polish expressions
+    // find any valid polish expressions, every operator needs one more operand than it has inputs
     let mut polish_expression: Vec<String> = Vec::new();
     let (mut flag, mut start_index, mut index) = (false, 0, 0);
-    let (mut ops, mut oprs) = (0, 0);
+    let (mut needed, mut oprs) = (1, 0);
     for token in &tokens {
         if operators.contains(&token.as_str()) {
             if !flag {
                 start_index += index;
                 flag = true;
             }
-            ops += 1;
+            needed += if token == "HSV" { 2 } else { 1 };
             polish_expression.push(token.clone());
-        } else if flag && oprs != (ops + 1) {
+        } else if flag && oprs != needed {
             oprs += 1;
             polish_expression.push(token.clone());
         }
@@ -166,7 +186,18 @@ pub fn evaluate_polish(mut tokens: Vec<String>) -> Result<Vec<String>, LocError>
     polish_expression.reverse();
     let mut stack: Vec<String> = Vec::new();
     for token in polish_expression {
-        if operators.contains(&token.as_str()) && flag {
+        if token == "HSV" && flag {
+            if stack.len() < 3 {
+                return Err(LocError::new(
+                    "Not enough operands, invalid expression",
+                    *Location::caller(),
+                ));
+            }
+            let hue = stack.pop().unwrap();
+            let saturation = stack.pop().unwrap();
+            let value = stack.pop().unwrap();
+            stack.push(Operation::hsv(hue, saturation, value)?);
+        } else if operators.contains(&token.as_str()) && flag {
             if stack.len() < 2 {
                 return Err(LocError::new(
                     "Not enough operands, invalid expression",
@@ -194,16 +225,12 @@ pub fn evaluate_polish(mut tokens: Vec<String>) -> Result<Vec<String>, LocError>
         }
     }
 
-    // if a polish expression was resolved, add it into the tokens
+    // if a polish expression was resolved, add it into the tokens in place of the expression
     if stack.len() == 1 {
-        let mut len = 0;
         tokens = tokens
             .into_iter()
             .enumerate()
-            .filter(|(index, _)| {
-                len += 1;
-                *index < start_index || len > (exp_len + 1)
-            })
+            .filter(|(index, _)| *index < start_index || *index >= start_index + exp_len)
             .map(|(_, token)| token)
             .collect();
         tokens.insert(start_index, stack.pop().unwrap());
@@ -0,0 +1,268 @@
+/*
+    A tiny built-in stroke font so LABEL can draw text with nothing but lines.
+    Every glyph is a list of strokes on a grid 4 units wide and 6 units tall, with y
+    pointing up from the baseline. Lowercase letters reuse the capitals at a smaller size.
+*/
+type Glyph = &'static [&'static [(i8, i8)]];
+
+// Width of a glyph in grid units, including the gap before the next one.
+pub const ADVANCE: f32 = 6.0;
+
+// Height of a capital letter in grid units.
+pub const CAP_HEIGHT: f32 = 6.0;
+
+// How big lowercase letters are compared to capitals.
+pub const LOWERCASE_SCALE: f32 = 0.7;
+
+/*
+    Lays out text as strokes of points. The text sits on a baseline starting at the
+    position, and is turned so the tops of the letters point along the heading.
+    Mirrored text runs backwards along the baseline, the way a flipped image shows it.
+*/
+pub fn text_strokes(
+    position: (f32, f32),
+    text: &str,
+    height: f32,
+    heading: f32,
+    mirrored: bool,
+) -> Vec<Vec<(f32, f32)>> {
+    let unit = height / CAP_HEIGHT;
+    let (sin_h, cos_h) = heading.to_radians().sin_cos();
+    let across = if mirrored { -1.0 } else { 1.0 };
+    let mut strokes = Vec::new();
+    let mut cursor = 0.0;
+    for c in text.chars() {
+        let scale = if c.is_lowercase() {
+            unit * LOWERCASE_SCALE
+        } else {
+            unit
+        };
+        for stroke in glyph(c) {
+            strokes.push(
+                stroke
+                    .iter()
+                    .map(|&(x, y)| {
+                        let along = (cursor + x as f32 * scale) * across;
+                        let up = y as f32 * scale;
+                        (
+                            position.0 + along * cos_h + up * sin_h,
+                            position.1 + along * sin_h - up * cos_h,
+                        )
+                    })
+                    .collect(),
+            );
+        }
+        cursor += ADVANCE * scale;
+    }
+    strokes
+}
+
+pub fn glyph(c: char) -> Glyph {
+    match c.to_ascii_uppercase() {
+        ' ' => &[],
+        'A' => &[&[(0, 0), (0, 4), (2, 6), (4, 4), (4, 0)], &[(0, 3), (4, 3)]],
+        'B' => &[
+            &[(0, 0), (0, 6), (3, 6), (4, 5), (4, 4), (3, 3), (0, 3)],
+            &[(3, 3), (4, 2), (4, 1), (3, 0), (0, 0)],
+        ],
+        'C' => &[&[
+            (4, 5),
+            (3, 6),
+            (1, 6),
+            (0, 5),
+            (0, 1),
+            (1, 0),
+            (3, 0),
+            (4, 1),
+        ]],
+        'D' => &[&[(0, 0), (0, 6), (2, 6), (4, 4), (4, 2), (2, 0), (0, 0)]],
+        'E' => &[&[(4, 6), (0, 6), (0, 0), (4, 0)], &[(0, 3), (3, 3)]],
+        'F' => &[&[(4, 6), (0, 6), (0, 0)], &[(0, 3), (3, 3)]],
+        'G' => &[&[
+            (4, 5),
+            (3, 6),
+            (1, 6),
+            (0, 5),
+            (0, 1),
+            (1, 0),
+            (3, 0),
+            (4, 1),
+            (4, 3),
+            (2, 3),
+        ]],
+        'H' => &[&[(0, 0), (0, 6)], &[(4, 0), (4, 6)], &[(0, 3), (4, 3)]],
+        'I' => &[&[(1, 6), (3, 6)], &[(2, 6), (2, 0)], &[(1, 0), (3, 0)]],
+        'J' => &[&[(4, 6), (4, 1), (3, 0), (1, 0), (0, 1)]],
+        'K' => &[&[(0, 0), (0, 6)], &[(4, 6), (0, 2)], &[(1, 3), (4, 0)]],
+        'L' => &[&[(0, 6), (0, 0), (4, 0)]],
+        'M' => &[&[(0, 0), (0, 6), (2, 3), (4, 6), (4, 0)]],
+        'N' => &[&[(0, 0), (0, 6), (4, 0), (4, 6)]],
+        'O' => &[&[
+            (1, 0),
+            (0, 1),
+            (0, 5),
+            (1, 6),
+            (3, 6),
+            (4, 5),
+            (4, 1),
+            (3, 0),
+            (1, 0),
+        ]],
+        'P' => &[&[(0, 0), (0, 6), (3, 6), (4, 5), (4, 4), (3, 3), (0, 3)]],
+        'Q' => &[
+            &[
+                (1, 0),
+                (0, 1),
+                (0, 5),
+                (1, 6),
+                (3, 6),
+                (4, 5),
+                (4, 1),
+                (3, 0),
+                (1, 0),
+            ],
+            &[(2, 2), (4, 0)],
+        ],
+        'R' => &[
+            &[(0, 0), (0, 6), (3, 6), (4, 5), (4, 4), (3, 3), (0, 3)],
+            &[(2, 3), (4, 0)],
+        ],
+        'S' => &[&[
+            (4, 5),
+            (3, 6),
+            (1, 6),
+            (0, 5),
+            (0, 4),
+            (1, 3),
+            (3, 3),
+            (4, 2),
+            (4, 1),
+            (3, 0),
+            (1, 0),
+            (0, 1),
+        ]],
+        'T' => &[&[(0, 6), (4, 6)], &[(2, 6), (2, 0)]],
+        'U' => &[&[(0, 6), (0, 1), (1, 0), (3, 0), (4, 1), (4, 6)]],
+        'V' => &[&[(0, 6), (2, 0), (4, 6)]],
+        'W' => &[&[(0, 6), (1, 0), (2, 3), (3, 0), (4, 6)]],
+        'X' => &[&[(0, 0), (4, 6)], &[(0, 6), (4, 0)]],
+        'Y' => &[&[(0, 6), (2, 3), (4, 6)], &[(2, 3), (2, 0)]],
+        'Z' => &[&[(0, 6), (4, 6), (0, 0), (4, 0)]],
+        '0' => &[
+            &[
+                (1, 0),
+                (0, 1),
+                (0, 5),
+                (1, 6),
+                (3, 6),
+                (4, 5),
+                (4, 1),
+                (3, 0),
+                (1, 0),
+            ],
+            &[(0, 1), (4, 5)],
+        ],
+        '1' => &[&[(1, 5), (2, 6), (2, 0)], &[(1, 0), (3, 0)]],
+        '2' => &[&[(0, 5), (1, 6), (3, 6), (4, 5), (4, 4), (0, 0), (4, 0)]],
+        '3' => &[
+            &[
+                (0, 5),
+                (1, 6),
+                (3, 6),
+                (4, 5),
+                (4, 4),
+                (3, 3),
+                (4, 2),
+                (4, 1),
+                (3, 0),
+                (1, 0),
+                (0, 1),
+            ],
+            &[(1, 3), (3, 3)],
+        ],
+        '4' => &[&[(3, 0), (3, 6), (0, 2), (4, 2)]],
+        '5' => &[&[
+            (4, 6),
+            (0, 6),
+            (0, 3),
+            (3, 3),
+            (4, 2),
+            (4, 1),
+            (3, 0),
+            (0, 0),
+        ]],
+        '6' => &[&[
+            (3, 6),
+            (1, 6),
+            (0, 5),
+            (0, 1),
+            (1, 0),
+            (3, 0),
+            (4, 1),
+            (4, 2),
+            (3, 3),
+            (0, 3),
+        ]],
+        '7' => &[&[(0, 6), (4, 6), (1, 0)]],
+        '8' => &[&[
+            (1, 3),
+            (0, 4),
+            (0, 5),
+            (1, 6),
+            (3, 6),
+            (4, 5),
+            (4, 4),
+            (3, 3),
+            (1, 3),
+            (0, 2),
+            (0, 1),
+            (1, 0),
+            (3, 0),
+            (4, 1),
+            (4, 2),
+            (3, 3),
+        ]],
+        '9' => &[&[
+            (4, 3),
+            (1, 3),
+            (0, 4),
+            (0, 5),
+            (1, 6),
+            (3, 6),
+            (4, 5),
+            (4, 1),
+            (3, 0),
+            (1, 0),
+        ]],
+        '.' => &[&[(2, 0), (2, 1)]],
+        ',' => &[&[(2, 1), (1, -1)]],
+        ':' => &[&[(2, 1), (2, 2)], &[(2, 4), (2, 5)]],
+        ';' => &[&[(2, 4), (2, 5)], &[(2, 2), (1, 0)]],
+        '!' => &[&[(2, 6), (2, 2)], &[(2, 0), (2, 1)]],
+        '?' => &[
+            &[(0, 5), (1, 6), (3, 6), (4, 5), (4, 4), (2, 3), (2, 2)],
+            &[(2, 0), (2, 1)],
+        ],
+        '-' => &[&[(1, 3), (3, 3)]],
+        '+' => &[&[(0, 3), (4, 3)], &[(2, 1), (2, 5)]],
+        '*' => &[&[(2, 1), (2, 5)], &[(0, 2), (4, 4)], &[(0, 4), (4, 2)]],
+        '/' => &[&[(0, 0), (4, 6)]],
+        '=' => &[&[(0, 2), (4, 2)], &[(0, 4), (4, 4)]],
+        '<' => &[&[(4, 6), (0, 3), (4, 0)]],
+        '>' => &[&[(0, 6), (4, 3), (0, 0)]],
+        '(' => &[&[(3, 6), (1, 4), (1, 2), (3, 0)]],
+        ')' => &[&[(1, 6), (3, 4), (3, 2), (1, 0)]],
+        '_' => &[&[(0, 0), (4, 0)]],
+        '#' => &[
+            &[(1, 0), (1, 6)],
+            &[(3, 0), (3, 6)],
+            &[(0, 2), (4, 2)],
+            &[(0, 4), (4, 4)],
+        ],
+        '%' => &[&[(0, 0), (4, 6)], &[(0, 6), (0, 5)], &[(4, 1), (4, 0)]],
+        '\'' => &[&[(2, 6), (2, 4)]],
+        '"' => &[&[(1, 6), (1, 4)], &[(3, 6), (3, 4)]],
+        // Anything we don't have a glyph for is drawn as an empty box.
+        _ => &[&[(0, 0), (0, 6), (4, 6), (4, 0), (0, 0)]],
+    }
+}
@@ -1,7 +1,11 @@
+use crate::canvas::{Canvas, FillRule};
+use crate::colour::{self, Gradient, Spread};
 use crate::expression::evaluate_polish;
-use crate::turtle::{PenState, Turtle};
+use crate::shape::Shape;
+use crate::space::Projection;
+use crate::turtle::{EdgeMode, PenMode, PenState, Turtle, Turtles};
+use crate::viewport::Flip;
 use crate::Command;
-use crate::Image;
 use crate::LocError;
 
 use std::collections::HashMap;
@@ -15,9 +19,9 @@ struct Function {
 
 pub fn execute(
     commands: Vec<Command>,
-    image: &mut Image,
-    mut turtle: Turtle,
-) -> Result<(), LocError> {
+    canvas: &mut Canvas,
+    turtle: Turtle,
+) -> Result<Turtles, LocError> {
     // Create the collections of variables and functions!
     let mut variables: HashMap<String, String> = HashMap::new();
     let mut functions: Vec<Function> = Vec::new();
@@ -27,6 +31,10 @@ pub fn execute(
     let mut while_if_stack: Vec<usize> = Vec::new();
     let mut func_stack: Vec<usize> = Vec::new();
 
+    // All the turtles, and for each ASK block we are in: where it ends and who to go back to.
+    let mut turtles = Turtles::new(turtle);
+    let mut ask_stack: Vec<(usize, String)> = Vec::new();
+
     // Execute through the vec of commands
     loop {
         // Exit if all commands have been executed; otherwise get the next command and execute on it
@@ -36,28 +44,104 @@ pub fn execute(
 
         // Get the command to execute on this loop
         let command = commands.get(index).unwrap();
+        canvas.set_line(Some(command.line));
 
         // Check command for valid num of arguments, allows for unwrapping later!
         command.check_command()?;
 
         // get tokens for this specific command and evaluate any potential polish expressions
-        let mut tokens = command.get_tokens(&turtle, &variables)?;
+        let mut tokens = command.get_tokens(&turtles, canvas, &variables)?;
         tokens = evaluate_polish(tokens)?;
+        let turtle = turtles.active_mut();
 
         // Execute the specific behaviour of the command.
         match command.first_token() {
             "//" => (),
             "PENUP" => turtle.change_penstate(PenState::Up),
-            "PENDOWN" => turtle.change_penstate(PenState::Down),
-            "SETPENCOLOR" => match tokens.first().unwrap().parse::<usize>() {
-                Ok(code @ 0..=16) => turtle.change_colour(code),
+            first_arg @ ("PENDOWN" | "PENPAINT" | "PENERASE" | "PENREVERSE") => {
+                // Putting the pen down always sets how it draws, PENDOWN paints like PENPAINT
+                turtle.change_penstate(PenState::Down);
+                turtle.change_pen_mode(match first_arg {
+                    "PENERASE" => PenMode::Erase,
+                    "PENREVERSE" => PenMode::Reverse,
+                    _ => PenMode::Paint,
+                });
+            }
+            "SETPENCOLOR" => match parse_colour(&tokens, canvas) {
+                Some(colour) => turtle.change_colour(colour),
+                None => {
+                    return Err(LocError::new(
+                        "Invalid colour, colour must be an integer between 0 and 15, #rrggbb or [ r g b ]",
+                        *Location::caller(),
+                    ));
+                }
+            },
+            "SETFILLCOLOR" => match parse_colour(&tokens, canvas) {
+                Some(colour) => turtle.change_fill_colour(colour),
+                None => {
+                    return Err(LocError::new(
+                        "Invalid colour, colour must be an integer between 0 and 15, #rrggbb or [ r g b ]",
+                        *Location::caller(),
+                    ));
+                }
+            },
+            // Going back to a plain pen keeps whatever colour the gradient had got to
+            "SETPENGRADIENT" if tokens == ["OFF"] => {
+                let colour = turtle.colour;
+                turtle.change_colour(colour);
+            }
+            "SETPENGRADIENT" => {
+                let spread = match (
+                    tokens.first().unwrap().as_str(),
+                    tokens.get(1).map(|period| period.parse::<f32>()),
+                ) {
+                    ("ALONG", Some(Ok(period))) if period > 0.0 => Spread::Along(period),
+                    ("SEGMENT", Some(Ok(period))) if period > 0.0 => Spread::Segment(period),
+                    _ => {
+                        return Err(LocError::new(
+                            "Gradient must be OFF, or ALONG or SEGMENT with a period above 0",
+                            *Location::caller(),
+                        ));
+                    }
+                };
+                let stops: Option<Vec<colour::Rgb>> = without_brackets(&tokens[2..])
+                    .iter()
+                    .map(|word| {
+                        parse_colour(std::slice::from_ref(word), canvas).map(|c| canvas.rgb(c))
+                    })
+                    .collect();
+                match stops {
+                    Some(stops) if !stops.is_empty() => {
+                        turtle.change_gradient(canvas, Gradient::new(spread, stops))
+                    }
+                    _ => {
+                        return Err(LocError::new(
+                            "Gradient needs a list of at least one colour",
+                            *Location::caller(),
+                        ));
+                    }
+                }
+            }
+            "SETFILLRULE" => match tokens.first().unwrap().as_str() {
+                "evenodd" => turtle.change_fill_rule(FillRule::EvenOdd),
+                "nonzero" => turtle.change_fill_rule(FillRule::NonZero),
                 _ => {
                     return Err(LocError::new(
-                        "Invalid colour, colour must be an integer between 0 and 16",
+                        "Fill rule must be either evenodd or nonzero",
                         *Location::caller(),
                     ));
                 }
             },
+            "BEGINFILL" => turtle.begin_fill(),
+            "ENDFILL" => {
+                if !turtle.end_fill(canvas) {
+                    return Err(LocError::new(
+                        "ENDFILL without a matching BEGINFILL",
+                        *Location::caller(),
+                    ));
+                }
+            }
+            "FILL" => turtle.fill(canvas),
             first_arg @ ("FORWARD" | "BACK" | "LEFT" | "RIGHT") => {
                 let direction = match first_arg {
                     "BACK" => 180,
@@ -66,7 +150,7 @@ pub fn execute(
                     _ => 0,
                 };
                 match tokens.first().unwrap().parse::<f32>() {
-                    Ok(distance) => turtle.draw(image, direction, distance),
+                    Ok(distance) => turtle.draw(canvas, direction, distance)?,
                     Err(_) => {
                         return Err(LocError::new(
                             "Unable to convert to a float!",
@@ -75,6 +159,222 @@ pub fn execute(
                     }
                 }
             }
+            "ARC" | "ARCFORWARD" | "ELLIPSE" => {
+                let (first, second) = match parse_pair(&tokens) {
+                    Some(pair) => pair,
+                    None => {
+                        return Err(LocError::new(
+                            "Unable to convert to a float!",
+                            *Location::caller(),
+                        ));
+                    }
+                };
+                if !first.is_finite() || !second.is_finite() {
+                    return Err(LocError::new(
+                        "Arc sizes must be finite numbers",
+                        *Location::caller(),
+                    ));
+                }
+                match command.first_token() {
+                    "ARC" => turtle.arc(canvas, first, second),
+                    "ELLIPSE" => turtle.ellipse(canvas, first, second),
+                    _ if first.fract() == 0.0 => {
+                        turtle.arc_forward(canvas, first as i32, second)?
+                    }
+                    _ => {
+                        return Err(LocError::new(
+                            "Unable to convert to a number!",
+                            *Location::caller(),
+                        ));
+                    }
+                }
+            }
+            "CIRCLE" => match tokens.first().unwrap().parse::<f32>() {
+                Ok(radius) if radius.is_finite() => turtle.arc(canvas, 360.0, radius),
+                Ok(_) => {
+                    return Err(LocError::new(
+                        "Arc sizes must be finite numbers",
+                        *Location::caller(),
+                    ));
+                }
+                Err(_) => {
+                    return Err(LocError::new(
+                        "Unable to convert to a float!",
+                        *Location::caller(),
+                    ));
+                }
+            },
+            "LABEL" => {
+                // Words in a list are written with spaces between them.
+                turtle.label(canvas, &without_brackets(&tokens).join(" "));
+            }
+            "SETLABELFONT" => turtle.change_label_font(tokens.first().unwrap()),
+            "SETLABELHEIGHT" => match tokens.first().unwrap().parse::<f32>() {
+                Ok(height) if height > 0.0 => turtle.change_label_height(height),
+                _ => {
+                    return Err(LocError::new(
+                        "Label height must be a positive number",
+                        *Location::caller(),
+                    ));
+                }
+            },
+            "HOME" => {
+                turtle.home();
+                turtle.check_edges(canvas)?;
+            }
+            "SHOWTURTLE" | "ST" => turtle.change_visible(true),
+            "HIDETURTLE" | "HT" => turtle.change_visible(false),
+            "SETSHAPE" => match Shape::from_words(&without_brackets(&tokens)) {
+                Some(shape) => turtle.change_shape(shape),
+                None => {
+                    return Err(LocError::new(
+                        "Shape must be TRIANGLE, CIRCLE, SQUARE, ARROW or a list of at least three x y points",
+                        *Location::caller(),
+                    ));
+                }
+            },
+            "STAMP" => turtle.stamp(canvas),
+            "DOT" => match tokens.first().unwrap().parse::<f32>() {
+                Ok(size) if size >= 0.0 && size.is_finite() => turtle.dot(canvas, size),
+                _ => {
+                    return Err(LocError::new(
+                        "Dot size must be a finite number no less than 0",
+                        *Location::caller(),
+                    ));
+                }
+            },
+            "SETPIXEL" => turtle.set_pixel(canvas),
+            "PUSHTURTLE" => turtle.push_state(),
+            "POPTURTLE" => {
+                if !turtle.pop_state() {
+                    return Err(LocError::new(
+                        "POPTURTLE without a saved turtle to go back to",
+                        *Location::caller(),
+                    ));
+                }
+            }
+            "SETBACKGROUND" => match parse_colour(&tokens, canvas) {
+                Some(colour) => canvas.set_background(colour),
+                None => {
+                    return Err(LocError::new(
+                        "Invalid colour, colour must be an integer between 0 and 15, #rrggbb or [ r g b ]",
+                        *Location::caller(),
+                    ));
+                }
+            },
+            // An animation shows the drawing as it was just before it is wiped off.
+            "CLEAN" => {
+                canvas.take_frame(|canvas| turtles.draw_sprites(canvas));
+                canvas.clean();
+            }
+            "CLEARSCREEN" | "CS" => {
+                canvas.take_frame(|canvas| turtles.draw_sprites(canvas));
+                canvas.clean();
+                turtles.home_all();
+            }
+            "ORTHOGRAPHIC" => turtle.change_projection(Projection::Orthographic),
+            "PERSPECTIVE" => turtle.change_projection(Projection::Perspective),
+            first_arg @ ("UPPITCH" | "DOWNPITCH" | "LEFTROLL" | "RIGHTROLL") => {
+                let angle = match tokens.first().unwrap().parse::<i32>() {
+                    Ok(angle) => angle,
+                    Err(_) => {
+                        return Err(LocError::new(
+                            "Invalid angle, angle must be an integer",
+                            *Location::caller(),
+                        ));
+                    }
+                };
+                let turned = match first_arg {
+                    "UPPITCH" => turtle.pitch_up(angle),
+                    "DOWNPITCH" => turtle.pitch_up(-angle),
+                    "RIGHTROLL" => turtle.roll_right(angle),
+                    _ => turtle.roll_right(-angle),
+                };
+                if !turned {
+                    return Err(LocError::new(
+                        "Only a 3D turtle can pitch or roll, use ORTHOGRAPHIC or PERSPECTIVE first",
+                        *Location::caller(),
+                    ));
+                }
+            }
+            "SETZ" => match tokens.first().unwrap().parse::<f32>() {
+                Ok(z) if turtle.change_z(z) => turtle.check_edges(canvas)?,
+                Ok(_) => {
+                    return Err(LocError::new(
+                        "Only a 3D turtle has a z coordinate, use ORTHOGRAPHIC or PERSPECTIVE first",
+                        *Location::caller(),
+                    ));
+                }
+                Err(_) => {
+                    return Err(LocError::new(
+                        "Invalid z coordinate, must be a number",
+                        *Location::caller(),
+                    ));
+                }
+            },
+            "WINDOW" => turtle.change_edge_mode(EdgeMode::Window),
+            "WRAP" => {
+                turtle.change_edge_mode(EdgeMode::Wrap);
+                turtle.check_edges(canvas)?;
+            }
+            "FENCE" => {
+                turtle.change_edge_mode(EdgeMode::Fence);
+                turtle.check_edges(canvas)?;
+            }
+            "SETPOS" | "SETXY" | "SETSCRUNCH" | "SETORIGIN" => {
+                let position = match parse_pair(&without_brackets(&tokens)) {
+                    Some(pair) => pair,
+                    None => {
+                        return Err(LocError::new(
+                            "Unable to convert to a float!",
+                            *Location::caller(),
+                        ));
+                    }
+                };
+                match command.first_token() {
+                    "SETSCRUNCH" if position.0 == 0.0 || position.1 == 0.0 => {
+                        return Err(LocError::new(
+                            "Scrunch factors can't be 0",
+                            *Location::caller(),
+                        ));
+                    }
+                    "SETSCRUNCH" => turtle.change_scrunch(position),
+                    "SETORIGIN" => canvas.set_origin(position),
+                    _ => {
+                        turtle.set_pos(position);
+                        turtle.check_edges(canvas)?;
+                    }
+                }
+            }
+            "SETSCALE" => match tokens.first().unwrap().parse::<f32>() {
+                Ok(scale) if scale > 0.0 => canvas.set_scale(scale),
+                _ => {
+                    return Err(LocError::new(
+                        "Scale must be a number greater than 0",
+                        *Location::caller(),
+                    ));
+                }
+            },
+            "SETROTATION" => match tokens.first().unwrap().parse::<i32>() {
+                Ok(rotation) => canvas.set_rotation(rotation),
+                Err(_) => {
+                    return Err(LocError::new(
+                        "Unable to convert to a number!",
+                        *Location::caller(),
+                    ));
+                }
+            },
+            "SETFLIP" => match tokens.first().unwrap().as_str() {
+                "NONE" => canvas.set_flip(Flip::None),
+                "HORIZONTAL" => canvas.set_flip(Flip::Horizontal),
+                "VERTICAL" => canvas.set_flip(Flip::Vertical),
+                _ => {
+                    return Err(LocError::new(
+                        "Flip must be NONE, HORIZONTAL or VERTICAL",
+                        *Location::caller(),
+                    ));
+                }
+            },
             "TURN" => match tokens.first().unwrap().parse::<i32>() {
                 Ok(turn) => turtle.turn(turn),
                 Err(_) => {
@@ -94,7 +394,10 @@ pub fn execute(
                 }
             },
             "SETX" => match tokens.first().unwrap().parse::<f32>() {
-                Ok(x) => turtle.change_x(x),
+                Ok(x) => {
+                    turtle.change_x(x);
+                    turtle.check_edges(canvas)?;
+                }
                 Err(_) => {
                     return Err(LocError::new(
                         "Unable to convert to a float!",
@@ -103,7 +406,10 @@ pub fn execute(
                 }
             },
             "SETY" => match tokens.first().unwrap().parse::<f32>() {
-                Ok(y) => turtle.change_y(y),
+                Ok(y) => {
+                    turtle.change_y(y);
+                    turtle.check_edges(canvas)?;
+                }
                 Err(_) => {
                     return Err(LocError::new(
                         "Unable to convert to a float!",
@@ -121,7 +427,10 @@ pub fn execute(
                     Err(_) => {
                         // If the variable could not be passed as a
                         if let Some(value) = tokens.get(1) {
-                            if value.as_str() == "TRUE" || value.as_str() == "FALSE" {
+                            if value.as_str() == "TRUE"
+                                || value.as_str() == "FALSE"
+                                || colour::parse_hex(value).is_some()
+                            {
                                 variables.insert(name.clone(), value.clone());
                             } else {
                                 return Err(LocError::new(
@@ -208,9 +517,46 @@ pub fn execute(
                     while_if_stack.push(index);
                 }
             }
+            "NEWTURTLE" => {
+                if !turtles.add(tokens.first().unwrap()) {
+                    return Err(LocError::new(
+                        "A turtle with that name already exists",
+                        *Location::caller(),
+                    ));
+                }
+            }
+            "TELL" => {
+                if !turtles.tell(tokens.first().unwrap()) {
+                    return Err(LocError::new(
+                        "No turtle with that name found",
+                        *Location::caller(),
+                    ));
+                }
+            }
+            "ASK" => {
+                // Find the index of the ask block's "]".
+                let mut ask_end = 0;
+                if !find_conditional_end(&mut ask_end, &index, &commands) {
+                    return Err(LocError::new("No end of ask block!", *Location::caller()));
+                }
+
+                // Talk to the asked turtle until the "]", then go back to this one.
+                let previous = turtles.active_name().to_string();
+                if !turtles.tell(tokens.first().unwrap()) {
+                    return Err(LocError::new(
+                        "No turtle with that name found",
+                        *Location::caller(),
+                    ));
+                }
+                ask_stack.push((ask_end, previous));
+            }
             "]" => {
-                // Pop an index from the while-if stack and jump to it!
-                if let Some(stack_index) = while_if_stack.pop() {
+                if ask_stack.last().map(|(ask_end, _)| *ask_end) == Some(index) {
+                    // The end of an ask block, go back to the turtle we were talking to before.
+                    let (_, previous) = ask_stack.pop().unwrap();
+                    turtles.tell(&previous);
+                } else if let Some(stack_index) = while_if_stack.pop() {
+                    // Pop an index from the while-if stack and jump to it!
                     index = stack_index - 1;
                 }
             }
@@ -239,6 +585,7 @@ pub fn execute(
                 // Pop an index from the func stack and jump to it!
                 if let Some(stack_index) = func_stack.pop() {
                     index = stack_index;
+                    canvas.end_group();
                 }
             }
             value => {
@@ -269,9 +616,14 @@ pub fn execute(
                 }
                 func_stack.push(index);
                 index = func.func_index;
+                canvas.begin_group(&func.func_name);
             }
         };
         index += 1;
+
+        if canvas.frame_due() {
+            canvas.take_frame(|canvas| turtles.draw_sprites(canvas));
+        }
     }
 
     if !while_if_stack.is_empty() {
@@ -280,16 +632,57 @@ pub fn execute(
             *Location::caller(),
         ));
     }
-    Ok(())
+
+    // The turtles go on last, so they sit on top of the finished drawing.
+    canvas.set_line(None);
+    canvas.take_frame(|canvas| turtles.draw_sprites(canvas));
+    turtles.draw_sprites(canvas);
+    Ok(turtles)
+}
+
+// Parses the first two tokens as floats, for commands that take a pair of numbers.
+fn parse_pair(tokens: &[String]) -> Option<(f32, f32)> {
+    match (
+        tokens.first()?.parse::<f32>(),
+        tokens.get(1)?.parse::<f32>(),
+    ) {
+        (Ok(first), Ok(second)) => Some((first, second)),
+        _ => None,
+    }
+}
+
+// Reads a colour: a palette number from 0 to 15, a #rrggbb word or a [ r g b ] list.
+pub fn parse_colour(tokens: &[String], canvas: &mut Canvas) -> Option<usize> {
+    match without_brackets(tokens).as_slice() {
+        [word] => match word.parse::<usize>() {
+            Ok(colour @ 0..=15) => Some(colour),
+            Ok(_) => None,
+            Err(_) => colour::parse_hex(word).map(|rgb| canvas.colour(rgb)),
+        },
+        [red, green, blue] => {
+            let channel = |word: &String| word.parse::<u8>().ok();
+            Some(canvas.colour((channel(red)?, channel(green)?, channel(blue)?)))
+        }
+        _ => None,
+    }
+}
+
+// Drops the brackets around a list, leaving just the words inside it.
+fn without_brackets(tokens: &[String]) -> Vec<String> {
+    tokens
+        .iter()
+        .filter(|s| *s != "[" && *s != "]")
+        .cloned()
+        .collect()
 }
 
-// Finds the ending "]" of a conditional (IF/WHILE)
+// Finds the ending "]" of a block (IF/WHILE/ASK)
 fn find_conditional_end(curr_index: &mut usize, index: &usize, commands: &[Command]) -> bool {
     let mut found = false;
     let mut stack = 0;
     for command in commands {
         let first = command.first_token();
-        if *curr_index > *index && ((first == "WHILE") | (first == "IF")) {
+        if *curr_index > *index && ((first == "WHILE") | (first == "IF") | (first == "ASK")) {
             stack += 1;
         } else if *curr_index > *index && first == "]" {
             if stack == 0 {
@@ -0,0 +1,200 @@
+use std::fmt::Write;
+
+use crate::backend::number;
+use crate::canvas::{Canvas, FillRule};
+use crate::colour;
+use crate::display::Item;
+use crate::turtle::{PenMode, Turtles};
+
+/*
+    Writes the finished drawing as JSON, for scripts that want to look at what was drawn
+    rather than at pixels. Every item in the display list is written in image pixels
+    with its colour, the line of the program that drew it and the procedure call it was
+    in, then every turtle as it was when the program ended, also in image pixels. The
+    turtles' own shapes come from no line, so theirs is null.
+*/
+pub fn drawing_json(canvas: &Canvas, turtles: &Turtles, source: Option<&str>) -> String {
+    let (display, lines) = (&canvas.display, canvas.lines());
+    let colour = |index: Option<usize>| string(&colour::to_hex(display.rgb(index)));
+    let mut json = "{\n".to_string();
+    let _ = writeln!(
+        json,
+        "  \"source\": {},",
+        source.map_or("null".to_string(), string)
+    );
+    let _ = writeln!(json, "  \"width\": {},", display.width);
+    let _ = writeln!(json, "  \"height\": {},", display.height);
+    let _ = writeln!(json, "  \"background\": {},", colour(None));
+
+    // Which procedure call each item is in comes from the groups around it.
+    let mut groups: Vec<&str> = Vec::new();
+    let mut items = Vec::new();
+    for (item, line) in display.items.iter().zip(lines) {
+        let fields = match item {
+            Item::Group(name) => {
+                groups.push(name);
+                continue;
+            }
+            Item::EndGroup => {
+                groups.pop();
+                continue;
+            }
+            Item::Line {
+                start,
+                end,
+                colour: ink,
+            } => {
+                format!(
+                    "\"type\": \"line\", \"from\": {}, \"to\": {}, \"colour\": {}, \"width\": 1, \"erase\": {}",
+                    point(*start),
+                    point(*end),
+                    colour(*ink),
+                    ink.is_none()
+                )
+            }
+            Item::Arc {
+                centre,
+                radii,
+                rotation,
+                start,
+                sweep,
+                colour: ink,
+            } => format!(
+                "\"type\": \"arc\", \"centre\": {}, \"radii\": {}, \"rotation\": {}, \"start\": {}, \"sweep\": {}, \"colour\": {}, \"width\": 1, \"erase\": {}",
+                point(*centre),
+                point(*radii),
+                float(*rotation),
+                float(*start),
+                float(*sweep),
+                colour(*ink),
+                ink.is_none()
+            ),
+            Item::Polygon {
+                points,
+                rule,
+                colour: ink,
+            } => {
+                let points: Vec<String> = points.iter().map(|each| point(*each)).collect();
+                let rule = match rule {
+                    FillRule::EvenOdd => "evenodd",
+                    FillRule::NonZero => "nonzero",
+                };
+                format!(
+                    "\"type\": \"fill\", \"points\": [{}], \"rule\": \"{rule}\", \"colour\": {}",
+                    points.join(", "),
+                    colour(Some(*ink))
+                )
+            }
+            Item::Region { rows, colour: ink } => {
+                let rows: Vec<String> = rows
+                    .iter()
+                    .map(|(row, start, end)| format!("[{row}, {}, {}]", float(*start), float(*end)))
+                    .collect();
+                format!(
+                    "\"type\": \"region\", \"rows\": [{}], \"colour\": {}, \"erase\": {}",
+                    rows.join(", "),
+                    colour(*ink),
+                    ink.is_none()
+                )
+            }
+            Item::Text {
+                position,
+                text,
+                font,
+                height,
+                heading,
+                mirrored,
+                colour: ink,
+            } => format!(
+                "\"type\": \"label\", \"position\": {}, \"text\": {}, \"font\": {}, \"height\": {}, \"heading\": {}, \"mirrored\": {mirrored}, \"colour\": {}",
+                point(*position),
+                string(text),
+                string(font),
+                float(*height),
+                float(*heading),
+                colour(Some(*ink))
+            ),
+            Item::Dot {
+                centre,
+                radius,
+                colour: ink,
+            } => format!(
+                "\"type\": \"dot\", \"centre\": {}, \"radius\": {}, \"colour\": {}",
+                point(*centre),
+                float(*radius),
+                colour(Some(*ink))
+            ),
+        };
+        let line = line.map_or("null".to_string(), |line| line.to_string());
+        let group = groups
+            .last()
+            .map_or("null".to_string(), |group| string(group));
+        items.push(format!(
+            "    {{{fields}, \"line\": {line}, \"procedure\": {group}}}"
+        ));
+    }
+    let _ = writeln!(json, "  \"items\": {},", list(&items));
+
+    let turtles: Vec<String> = turtles
+        .iter()
+        .map(|(name, turtle)| {
+            let mode = match turtle.pen_mode() {
+                PenMode::Paint => "paint",
+                PenMode::Erase => "erase",
+                PenMode::Reverse => "reverse",
+            };
+            format!(
+                "    {{\"name\": {}, \"position\": {}, \"heading\": {}, \"pen_down\": {}, \"pen_mode\": \"{mode}\", \"colour\": {}, \"fill_colour\": {}, \"visible\": {}}}",
+                string(name),
+                point(canvas.view(turtle.coords)),
+                turtle.heading,
+                turtle.pen_down(),
+                colour(Some(turtle.colour)),
+                colour(Some(turtle.fill_colour)),
+                turtle.visible()
+            )
+        })
+        .collect();
+    let _ = writeln!(json, "  \"turtles\": {}", list(&turtles));
+    json.push_str("}\n");
+    json
+}
+
+// JSON has no way to write infinity or not a number, so those are null.
+fn float(value: f32) -> String {
+    if value.is_finite() {
+        number(value)
+    } else {
+        "null".to_string()
+    }
+}
+
+// An array with each entry on its own line.
+fn list(entries: &[String]) -> String {
+    if entries.is_empty() {
+        return "[]".to_string();
+    }
+    format!("[\n{}\n  ]", entries.join(",\n"))
+}
+
+fn point((x, y): (f32, f32)) -> String {
+    format!("[{}, {}]", float(x), float(y))
+}
+
+// Text as a JSON string, quoted and escaped.
+fn string(text: &str) -> String {
+    let mut json = "\"".to_string();
+    for character in text.chars() {
+        match character {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            character if character < ' ' => {
+                let _ = write!(json, "\\u{:04x}", character as u32);
+            }
+            character => json.push(character),
+        }
+    }
+    json.push('"');
+    json
+}
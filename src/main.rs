@@ -1,20 +1,39 @@
+mod animation;
+mod backend;
+mod canvas;
+mod colour;
 mod command;
+mod display;
 mod expression;
+mod font;
 mod interpreter;
+mod json;
 mod locationerror;
+mod shape;
+mod space;
 mod turtle;
+mod viewport;
 
 use clap::Parser;
 use locationerror::LocError;
-use unsvg::Image;
 
+use crate::backend::{
+    page_size, parse_length, rasterise, save_png, terminal_art, Charset, GcodeWriter, HpglWriter,
+    PdfWriter, Plotter, Raster, SvgWriter, Units, UnsvgImage,
+};
+use crate::canvas::{Canvas, Fit};
 use crate::command::{check_procedures, Command};
-use crate::interpreter::execute;
+use crate::interpreter::{execute, parse_colour};
 use crate::turtle::Turtle;
+use crate::viewport::Flip;
 
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Write};
 use std::panic::Location;
+use std::path::Path;
+
+// A png at this many dots to the inch has one dot for each pixel, as CSS pixels are.
+const CSS_DPI: f32 = 96.0;
 
 /// A simple program to parse four arguments using clap.
 #[derive(Parser)]
@@ -22,7 +41,7 @@ struct Tokens {
     /// Path to a file
     file_path: std::path::PathBuf,
 
-    /// Path to an svg or png image
+    /// Path to an svg, png, pdf, gcode, hpgl, plt, gif, apng or json file, or - to print to the terminal
     image_path: std::path::PathBuf,
 
     /// Height
@@ -30,14 +49,118 @@ struct Tokens {
 
     /// Width
     width: u32,
+
+    /// Background colour, a number from 0 to 15 or #rrggbb like SETPENCOLOR takes
+    #[arg(long, default_value = "0")]
+    background: String,
+
+    /// How many pixels one turtle step takes up
+    #[arg(long, default_value_t = 1.0)]
+    scale: f32,
+
+    /// Moves the drawing right and up by this many pixels
+    #[arg(long, num_args = 2, value_names = ["X", "Y"], allow_negative_numbers = true)]
+    origin: Option<Vec<f32>>,
+
+    /// Turns the drawing clockwise by this many degrees
+    #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+    rotate: i32,
+
+    /// Mirrors the drawing
+    #[arg(long, default_value = "none", value_parser = ["none", "horizontal", "vertical"])]
+    flip: String,
+
+    /// Run the program twice, sizing the image to the drawing or scaling the drawing to the image
+    #[arg(long, num_args = 0..=1, default_missing_value = "size", value_parser = ["size", "scale"])]
+    fit: Option<String>,
+
+    /// Pixels to leave around the drawing with --fit or --crop
+    #[arg(long, default_value_t = 10.0)]
+    margin: f32,
+
+    /// Sets an svg's viewBox to show just the drawing
+    #[arg(long)]
+    crop: bool,
+
+    /// Page size for a pdf: A3, A4, A5, Letter, Legal or a size like 210x297mm
+    #[arg(long)]
+    page: Option<String>,
+
+    /// How big each pixel is in a pdf, G-code or HPGL, like 0.5mm, 0.01in or 1pt
+    #[arg(long)]
+    pixel_size: Option<String>,
+
+    /// Units for G-code
+    #[arg(long, default_value = "mm", value_parser = ["mm", "in"])]
+    units: String,
+
+    /// How far a G-code or HPGL machine reaches, as a page size like 300x218mm or A4
+    #[arg(long)]
+    bed: Option<String>,
+
+    /// G-code drawing speed in units per minute [default: 1000mm]
+    #[arg(long)]
+    feed: Option<f32>,
+
+    /// G-code speed between strokes in units per minute, rapid moves are used without it
+    #[arg(long)]
+    travel_feed: Option<f32>,
+
+    /// G-code command that lifts the pen
+    #[arg(long, default_value = "M5")]
+    pen_up: String,
+
+    /// G-code command that puts the pen down
+    #[arg(long, default_value = "M3")]
+    pen_down: String,
+
+    /// Animate the drawing: an svg that plays itself, or a png for each frame. Always on for gif and apng
+    #[arg(long)]
+    animate: bool,
+
+    /// How many lines, arcs or fills to draw between frames of an animation
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    every: u32,
+
+    /// How long each frame of an animation shows for in milliseconds, the last is held for at least two seconds
+    #[arg(long, default_value_t = 50)]
+    delay: u32,
+
+    /// What to write, instead of going by the image path's extension
+    #[arg(long, value_parser = ["svg", "png", "pdf", "gcode", "hpgl", "gif", "apng", "ascii", "json"])]
+    format: Option<String>,
+
+    /// Characters to draw with in the terminal
+    #[arg(long, default_value = "braille", value_parser = ["braille", "blocks", "ascii"])]
+    charset: String,
+
+    /// Print in the terminal in the pen colours
+    #[arg(long)]
+    colour: bool,
+
+    /// Resolution of a png in dots per inch, 96 gives one dot for each pixel so 384 draws it 4 times as big
+    #[arg(long)]
+    dpi: Option<f32>,
+
+    /// Draw a png this many times as big and average it back down, for smoother edges
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=16))]
+    supersample: u32,
+
+    /// Leave a png's background see-through
+    #[arg(long)]
+    transparent: bool,
+
+    /// How many characters wide to print in the terminal, one for every two pixels without it
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    columns: Option<u32>,
 }
 
 fn main() -> Result<(), LocError> {
     let tokens: Tokens = Tokens::parse();
 
     // Access the parsed arguments
-    let file_path = tokens.file_path;
-    let image_path = tokens.image_path;
+    let file_path = tokens.file_path.clone();
+    let image_path = tokens.image_path.clone();
     let height = tokens.height;
     let width = tokens.width;
 
@@ -53,11 +176,11 @@ fn main() -> Result<(), LocError> {
     };
     let reader = BufReader::new(file);
     let mut commands: Vec<Command> = Vec::new();
-    for line in reader.lines() {
+    for (number, line) in reader.lines().enumerate() {
         let line = line.unwrap();
         if !line.trim().is_empty() {
             let line: Vec<String> = line.split_whitespace().map(|s| s.to_string()).collect();
-            commands.push(Command::new(line));
+            commands.push(Command::new(line, number + 1));
         }
     }
 
@@ -65,15 +188,153 @@ fn main() -> Result<(), LocError> {
     check_procedures(&commands)?;
 
     // execute the functionality of all the commands.
-    let turtle = Turtle::new((height as f32 / 2.0, width as f32 / 2.0));
-    let mut image = Image::new(width, height);
-    execute(commands, &mut image, turtle)?;
+    let mut canvas = Canvas::new(width, height);
+    set_up_canvas(&mut canvas, &tokens)?;
+    let turtle = Turtle::new(canvas.home());
 
-    // save the image to the correct path - its updated here
-    match image_path.extension().and_then(|s| s.to_str()) {
+    // A first run finds out how big the drawing is, then it is drawn again to fit.
+    let turtles = if let Some(fit) = tokens.fit.as_deref() {
+        let fit = if fit == "scale" {
+            Fit::Scale
+        } else {
+            Fit::Size
+        };
+        execute(commands.clone(), &mut canvas, turtle)?;
+        canvas = canvas.fitted(fit, tokens.margin.max(0.0))?;
+        set_up_canvas(&mut canvas, &tokens)?;
+        let turtle = Turtle::new(canvas.home());
+        execute(commands, &mut canvas, turtle)?
+    } else {
+        execute(commands, &mut canvas, turtle)?
+    };
+
+    // draw everything onto an image and save it to the correct path
+    let display = &canvas.display;
+    let format = output_format(&tokens);
+    if image_path.as_os_str() == "-" && matches!(format.as_deref(), Some("png" | "gif" | "apng")) {
+        return Err(LocError::new(
+            "A png, gif or apng can't be printed to the terminal, give an image path",
+            *Location::caller(),
+        ));
+    }
+    match format.as_deref() {
         Some("svg") => {
-            let res = image.save_svg(&image_path);
-            if res.is_err() {
+            let mut svg = SvgWriter::new(display.width, display.height);
+            if let Some(name) = tokens.file_path.file_name() {
+                svg.set_source(&name.to_string_lossy());
+            }
+            if let (true, Some((low, high))) = (tokens.crop, canvas.extent()) {
+                let margin = tokens.margin.max(0.0);
+                svg.set_view_box(
+                    (low.0 - margin, low.1 - margin),
+                    (high.0 + margin, high.1 + margin),
+                );
+            }
+            match &canvas.recording {
+                Some(recording) => animation::write_svg(&mut svg, recording, display, tokens.delay),
+                None => display.render(&mut svg),
+            }
+            write_output(&image_path, svg.finish())?;
+        }
+        Some("pdf") => {
+            let landscape = display.width > display.height;
+            let page = match tokens.page.as_deref().map(|page| page_size(page, landscape)) {
+                Some(None) => {
+                    return Err(LocError::new(
+                        "Invalid page size, page size must be A3, A4, A5, Letter, Legal or like 210x297mm",
+                        *Location::caller(),
+                    ))
+                }
+                page => page.flatten(),
+            };
+            let pixel = pixel_size(&tokens)?;
+            let mut pdf = PdfWriter::new(display.width, display.height, page, pixel);
+            if let Some(name) = tokens.file_path.file_name() {
+                pdf.set_source(&name.to_string_lossy());
+            }
+            display.render(&mut pdf);
+            write_output(&image_path, pdf.finish())?;
+        }
+        Some("gcode") => {
+            let units = match tokens.units.as_str() {
+                "in" => Units::Inches,
+                _ => Units::Millimetres,
+            };
+            let bed = bed_size(&tokens, display.width > display.height)?;
+            let plotter = Plotter {
+                units,
+                bed: bed.map(|bed| (bed.0 / units.points(), bed.1 / units.points())),
+                pixel: pixel_size(&tokens)?.map(|pixel| pixel / units.points()),
+                feed: tokens
+                    .feed
+                    .unwrap_or(1000.0 * Units::Millimetres.points() / units.points()),
+                travel_feed: tokens.travel_feed,
+                pen_up: tokens.pen_up.clone(),
+                pen_down: tokens.pen_down.clone(),
+            };
+            let Some(mut gcode) = GcodeWriter::new(display.width, display.height, plotter) else {
+                return Err(LocError::new(
+                    "The image is too big for the bed at that pixel size",
+                    *Location::caller(),
+                ));
+            };
+            display.render(&mut gcode);
+            let source = tokens
+                .file_path
+                .file_name()
+                .map(|name| name.to_string_lossy());
+            write_output(&image_path, gcode.finish(source.as_deref()))?;
+        }
+        Some("hpgl") => {
+            let bed = bed_size(&tokens, display.width > display.height)?;
+            let pixel = pixel_size(&tokens)?;
+            let palette = display.palette.clone();
+            let Some(mut hpgl) =
+                HpglWriter::new(display.width, display.height, palette, bed, pixel)
+            else {
+                return Err(LocError::new(
+                    "The image is too big for the bed at that pixel size",
+                    *Location::caller(),
+                ));
+            };
+            display.render(&mut hpgl);
+            write_output(&image_path, hpgl.finish())?;
+        }
+        Some("gif") => {
+            if let Some(recording) = &canvas.recording {
+                animation::save_gif(&image_path, recording, display, tokens.delay)?;
+            }
+        }
+        Some("apng") => {
+            if let Some(recording) = &canvas.recording {
+                animation::save_apng(&image_path, recording, display, tokens.delay)?;
+            }
+        }
+        Some("png") if canvas.recording.is_some() => {
+            if let Some(recording) = &canvas.recording {
+                animation::save_frames(&image_path, recording, display)?;
+            }
+        }
+        Some("png") if tokens.dpi.is_some() || tokens.supersample > 1 || tokens.transparent => {
+            let dpi = tokens.dpi.unwrap_or(CSS_DPI);
+            if !(dpi.is_finite() && dpi > 0.0) {
+                return Err(LocError::new(
+                    "DPI must be a number greater than 0",
+                    *Location::caller(),
+                ));
+            }
+            let raster = Raster {
+                scale: dpi / CSS_DPI,
+                supersample: tokens.supersample,
+                background: !tokens.transparent,
+            };
+            let Some(pixmap) = rasterise(display, &raster) else {
+                return Err(LocError::new(
+                    "The image is too big to draw at that resolution",
+                    *Location::caller(),
+                ));
+            };
+            if save_png(&image_path, &pixmap, tokens.dpi).is_err() {
                 return Err(LocError::new(
                     "couldn't save to image path",
                     *Location::caller(),
@@ -81,7 +342,9 @@ fn main() -> Result<(), LocError> {
             }
         }
         Some("png") => {
-            let res = image.save_png(&image_path);
+            let mut image = UnsvgImage::new(display.width, display.height);
+            display.render(&mut image);
+            let res = image.image.save_png(&image_path);
             if res.is_err() {
                 return Err(LocError::new(
                     "couldn't save to image path",
@@ -89,6 +352,39 @@ fn main() -> Result<(), LocError> {
                 ));
             }
         }
+        Some("json") => {
+            let source = tokens
+                .file_path
+                .file_name()
+                .map(|name| name.to_string_lossy());
+            let json = json::drawing_json(&canvas, &turtles, source.as_deref());
+            write_output(&image_path, json)?;
+        }
+        Some("ascii") => {
+            let charset = match tokens.charset.as_str() {
+                "blocks" => Charset::Blocks,
+                "ascii" => Charset::Ascii,
+                _ => Charset::Braille,
+            };
+            // Each character stands for a few pixels, so the picture is resized to fit the columns.
+            let scale = tokens.columns.map_or(1.0, |columns| {
+                (columns * charset.cell().0) as f32 / display.width.max(1) as f32
+            });
+            let Some(pixmap) = rasterise(display, &Raster::new(scale)) else {
+                return Err(LocError::new(
+                    "couldn't draw the image for the terminal",
+                    *Location::caller(),
+                ));
+            };
+            let art = terminal_art(
+                &pixmap,
+                display.rgb(None),
+                &display.palette,
+                charset,
+                tokens.colour,
+            );
+            write_output(&image_path, art)?;
+        }
         _ => {
             return Err(LocError::new(
                 "File extension not supported",
@@ -98,3 +394,84 @@ fn main() -> Result<(), LocError> {
     }
     Ok(())
 }
+
+// What to write: --format if given, or else what the image path's extension says. A path of - prints to the terminal.
+fn output_format(tokens: &Tokens) -> Option<String> {
+    if let Some(format) = &tokens.format {
+        return Some(format.clone());
+    }
+    if tokens.image_path.as_os_str() == "-" {
+        return Some("ascii".to_string());
+    }
+    let extension = tokens.image_path.extension().and_then(|s| s.to_str())?;
+    Some(match extension {
+        "plt" => "hpgl".to_string(),
+        extension => extension.to_string(),
+    })
+}
+
+// Writes out a finished file, or prints it when the image path is -.
+fn write_output(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), LocError> {
+    let res = if path.as_os_str() == "-" {
+        std::io::stdout().write_all(contents.as_ref())
+    } else {
+        std::fs::write(path, contents)
+    };
+    res.map_err(|_| LocError::new("couldn't save to image path", *Location::caller()))
+}
+
+// Reads --bed as points, turning a named size on its side for landscape images.
+fn bed_size(tokens: &Tokens, landscape: bool) -> Result<Option<(f32, f32)>, LocError> {
+    match tokens.bed.as_deref().map(|bed| page_size(bed, landscape)) {
+        Some(None) => Err(LocError::new(
+            "Invalid bed size, bed size must be A3, A4, A5, Letter, Legal or like 300x218mm",
+            *Location::caller(),
+        )),
+        bed => Ok(bed.flatten()),
+    }
+}
+
+// Reads --pixel-size as points.
+fn pixel_size(tokens: &Tokens) -> Result<Option<f32>, LocError> {
+    match tokens.pixel_size.as_deref().map(parse_length) {
+        Some(None) => Err(LocError::new(
+            "Invalid pixel size, pixel size must be a length like 0.5mm",
+            *Location::caller(),
+        )),
+        pixel => Ok(pixel.flatten()),
+    }
+}
+
+// Applies the background and viewport given on the command line.
+fn set_up_canvas(canvas: &mut Canvas, tokens: &Tokens) -> Result<(), LocError> {
+    if tokens.scale <= 0.0 {
+        return Err(LocError::new(
+            "Scale must be a number greater than 0",
+            *Location::caller(),
+        ));
+    }
+    match parse_colour(std::slice::from_ref(&tokens.background), canvas) {
+        Some(colour) => canvas.set_background(colour),
+        None => {
+            return Err(LocError::new(
+                "Background colour must be an integer between 0 and 15 or #rrggbb",
+                *Location::caller(),
+            ));
+        }
+    }
+    canvas.set_scale(tokens.scale);
+    if let Some(origin) = &tokens.origin {
+        canvas.set_origin((origin[0], origin[1]));
+    }
+    canvas.set_rotation(tokens.rotate);
+    canvas.set_flip(match tokens.flip.as_str() {
+        "horizontal" => Flip::Horizontal,
+        "vertical" => Flip::Vertical,
+        _ => Flip::None,
+    });
+    // Recording starts last, so the first frame already has everything set up.
+    if tokens.animate || matches!(output_format(tokens).as_deref(), Some("gif" | "apng")) {
+        canvas.record(tokens.every as usize);
+    }
+    Ok(())
+}
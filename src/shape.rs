@@ -0,0 +1,59 @@
+use crate::canvas::arc_points;
+
+/*
+    The shapes a turtle can be drawn as. Outlines are given relative to the turtle, with x
+    pointing to its right and y pointing the way it faces, so they turn along with it.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Triangle,
+    Circle,
+    Square,
+    Arrow,
+    // Any closed outline the user gives with SETSHAPE.
+    Polygon(Vec<(f32, f32)>),
+}
+
+impl Shape {
+    // Picks a shape from the words given to SETSHAPE: a shape name, or a list of x y pairs.
+    pub fn from_words(words: &[String]) -> Option<Self> {
+        if let [name] = words {
+            return match name.as_str() {
+                "TRIANGLE" => Some(Shape::Triangle),
+                "CIRCLE" => Some(Shape::Circle),
+                "SQUARE" => Some(Shape::Square),
+                "ARROW" => Some(Shape::Arrow),
+                _ => None,
+            };
+        }
+        if words.len() < 6 || !words.len().is_multiple_of(2) {
+            return None;
+        }
+        let numbers = words
+            .iter()
+            .map(|word| word.parse::<f32>().ok())
+            .collect::<Option<Vec<f32>>>()?;
+        Some(Shape::Polygon(
+            numbers.chunks(2).map(|pair| (pair[0], pair[1])).collect(),
+        ))
+    }
+
+    pub fn outline(&self) -> Vec<(f32, f32)> {
+        match self {
+            // Pointing the way the turtle faces, with the turtle in the middle.
+            Shape::Triangle => vec![(0.0, 10.0), (6.0, -5.0), (-6.0, -5.0)],
+            Shape::Circle => arc_points((0.0, 0.0), (8.0, 8.0), 0.0, 0.0, 360.0),
+            Shape::Square => vec![(-7.0, 7.0), (7.0, 7.0), (7.0, -7.0), (-7.0, -7.0)],
+            Shape::Arrow => vec![
+                (0.0, 12.0),
+                (8.0, 2.0),
+                (3.0, 2.0),
+                (3.0, -10.0),
+                (-3.0, -10.0),
+                (-3.0, 2.0),
+                (-8.0, 2.0),
+            ],
+            Shape::Polygon(points) => points.clone(),
+        }
+    }
+}
@@ -0,0 +1,175 @@
+/*
+    Geometry for turtles that move in three dimensions.
+    Positions are in turtle space with x to the right, y up and z coming out of the
+    screen towards the viewer, and get projected down to the flat turtle space the
+    rest of the turtle works in.
+*/
+pub type Vector = (f32, f32, f32);
+
+// How points in three dimensions are flattened onto the canvas.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    // Straight on, so distance from the screen makes no difference.
+    Orthographic,
+    // From an eye in front of the screen, so things further away look smaller.
+    Perspective,
+}
+
+// How far in front of the screen the eye is for the perspective projection, in turtle steps.
+pub const EYE_DISTANCE: f32 = 600.0;
+
+// How close to the eye something can be and still get drawn.
+const NEAR: f32 = 1.0;
+
+// Which way the turtle faces, and which way its back points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Orientation {
+    forward: Vector,
+    up: Vector,
+}
+
+impl Orientation {
+    // Lying flat on the screen facing the heading, with its back to the viewer like a 2D turtle.
+    pub fn flat(heading: f32) -> Self {
+        let (sin, cos) = heading.to_radians().sin_cos();
+        Orientation {
+            forward: (sin, cos, 0.0),
+            up: (0.0, 0.0, 1.0),
+        }
+    }
+
+    pub fn forward(&self) -> Vector {
+        self.forward
+    }
+
+    pub fn right(&self) -> Vector {
+        cross(self.forward, self.up)
+    }
+
+    // Turn right about the turtle's back, like TURN does for a flat turtle.
+    pub fn turn(&mut self, degrees: f32) {
+        let right = self.right();
+        self.forward = rotate_towards(self.forward, right, degrees);
+        self.tidy();
+    }
+
+    // Tip the turtle's nose up towards its back.
+    pub fn pitch_up(&mut self, degrees: f32) {
+        let (forward, up) = (self.forward, self.up);
+        self.forward = rotate_towards(forward, up, degrees);
+        self.up = rotate_towards(up, scale(forward, -1.0), degrees);
+        self.tidy();
+    }
+
+    // Roll over onto the turtle's right side.
+    pub fn roll_right(&mut self, degrees: f32) {
+        let right = self.right();
+        self.up = rotate_towards(self.up, right, degrees);
+        self.tidy();
+    }
+
+    // The compass heading of where the turtle faces, as seen looking at the screen.
+    pub fn heading(&self) -> f32 {
+        self.forward
+            .0
+            .atan2(self.forward.1)
+            .to_degrees()
+            .rem_euclid(360.0)
+    }
+
+    // How far the nose points out of the screen, from -90 to 90.
+    pub fn pitch(&self) -> f32 {
+        self.forward.2.clamp(-1.0, 1.0).asin().to_degrees()
+    }
+
+    // How far the turtle has rolled right from having its back as close to the viewer as it can.
+    pub fn roll(&self) -> f32 {
+        let level_right = cross(self.forward, (0.0, 0.0, 1.0));
+        if length(level_right) < 1e-6 {
+            return 0.0;
+        }
+        let level_right = scale(level_right, 1.0 / length(level_right));
+        let level_up = cross(level_right, self.forward);
+        dot(self.up, level_right)
+            .atan2(dot(self.up, level_up))
+            .to_degrees()
+            .rem_euclid(360.0)
+    }
+
+    // Keeps the two directions at right angles and of unit length as rounding errors build up.
+    fn tidy(&mut self) {
+        self.forward = scale(self.forward, 1.0 / length(self.forward));
+        let up = add(self.up, scale(self.forward, -dot(self.up, self.forward)));
+        self.up = scale(up, 1.0 / length(up));
+    }
+}
+
+/*
+    Flattens a point into flat turtle space. Perspective can't show anything at or
+    behind the eye, so there is nothing to give back for those points.
+*/
+pub fn project(point: Vector, projection: Projection) -> Option<(f32, f32)> {
+    match projection {
+        Projection::Orthographic => Some((point.0, point.1)),
+        Projection::Perspective if point.2 > EYE_DISTANCE - NEAR => None,
+        Projection::Perspective => {
+            let scale = EYE_DISTANCE / (EYE_DISTANCE - point.2);
+            Some((point.0 * scale, point.1 * scale))
+        }
+    }
+}
+
+// Projects a line, cutting off any part of it too close to the eye to be seen.
+pub fn project_line(
+    start: Vector,
+    end: Vector,
+    projection: Projection,
+) -> Option<((f32, f32), (f32, f32))> {
+    let limit = EYE_DISTANCE - NEAR;
+    let (mut start, mut end) = (start, end);
+    if projection == Projection::Perspective {
+        if start.2 > limit && end.2 > limit {
+            return None;
+        }
+        let cut = |inside: Vector, outside: Vector| {
+            let along = (limit - inside.2) / (outside.2 - inside.2);
+            add(inside, scale(add(outside, scale(inside, -1.0)), along))
+        };
+        if start.2 > limit {
+            start = cut(end, start);
+        } else if end.2 > limit {
+            end = cut(start, end);
+        }
+    }
+    Some((project(start, projection)?, project(end, projection)?))
+}
+
+pub fn add(a: Vector, b: Vector) -> Vector {
+    (a.0 + b.0, a.1 + b.1, a.2 + b.2)
+}
+
+pub fn scale(a: Vector, by: f32) -> Vector {
+    (a.0 * by, a.1 * by, a.2 * by)
+}
+
+fn dot(a: Vector, b: Vector) -> f32 {
+    a.0 * b.0 + a.1 * b.1 + a.2 * b.2
+}
+
+fn cross(a: Vector, b: Vector) -> Vector {
+    (
+        a.1 * b.2 - a.2 * b.1,
+        a.2 * b.0 - a.0 * b.2,
+        a.0 * b.1 - a.1 * b.0,
+    )
+}
+
+fn length(a: Vector) -> f32 {
+    dot(a, a).sqrt()
+}
+
+// Swings a direction round towards another one at right angles to it.
+fn rotate_towards(from: Vector, towards: Vector, degrees: f32) -> Vector {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    add(scale(from, cos), scale(towards, sin))
+}
@@ -1,25 +1,127 @@
-use unsvg::{get_end_coordinates, Image, COLORS};
+use std::panic::Location;
+use unsvg::get_end_coordinates;
 
+use crate::canvas::{arc_points, wound, Canvas, FillRule, Ink};
+use crate::colour::{Gradient, Spread};
+use crate::locationerror::LocError;
+use crate::shape::Shape;
+use crate::space::{add, project, project_line, scale, Orientation, Projection, Vector};
+
+/*
+    The turtle's coords are image pixels, which is what SETX, SETY, XCOR and YCOR use.
+    Commands like SETPOS and TOWARDS instead work in turtle space: centred on the turtle's
+    home with y pointing up, and stretched by the scrunch factors. Moves like FORWARD are
+    stretched the same way, but arcs, labels and dots keep their shape.
+    A 3D turtle keeps its real position in solid, and its coords are where that lands
+    on the canvas once projected.
+*/
 pub struct Turtle {
     pub coords: (f32, f32),
+    home: (f32, f32),
+    pub scrunch: (f32, f32),
     pub colour: usize,
     pen_state: PenState,
+    pen_mode: PenMode,
     pub heading: i32,
+    pub fill_colour: usize,
+    pub fill_rule: FillRule,
+    fill_path: Option<Vec<(f32, f32)>>,
+    pub label_font: String,
+    pub label_height: f32,
+    edge_mode: EdgeMode,
+    saved: Vec<SavedState>,
+    visible: bool,
+    pub shape: Shape,
+    solid: Option<Solid>,
+    gradient: Option<Gradient>,
 }
 
-#[derive(Debug, PartialEq)]
+// How long each piece of a line is when the colour changes along it, in turtle steps.
+const GRADIENT_PIECE: f32 = 2.0;
+
+// The most pieces one line or arc is split into, however long it is; past that they get longer.
+const MAX_GRADIENT_PIECES: usize = 10_000;
+
+// The most times a wrapping line crosses the canvas before it is taken to be going over itself.
+const MAX_WRAPS: u32 = 1000;
+
+// Where a 3D turtle is, which way it is facing and how it is shown on the canvas.
+#[derive(Debug, Clone, Copy)]
+struct Solid {
+    position: Vector,
+    orientation: Orientation,
+    projection: Projection,
+}
+
+/*
+    Everything PUSHTURTLE remembers so POPTURTLE can put the turtle back: where it is and
+    how its pen, fills and labels draw. Pens are always a pixel wide, so there is no
+    width to keep.
+*/
+struct SavedState {
+    coords: (f32, f32),
+    heading: i32,
+    pen_state: PenState,
+    pen_mode: PenMode,
+    colour: usize,
+    gradient: Option<Gradient>,
+    fill_colour: usize,
+    fill_rule: FillRule,
+    label_font: String,
+    label_height: f32,
+    solid: Option<Solid>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PenState {
     Up,
     Down,
 }
 
+// How the pen marks the canvas while it is down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PenMode {
+    Paint,
+    Erase,
+    Reverse,
+}
+
+/*
+    What happens when the turtle reaches the edge of the canvas. Only moving the turtle
+    is checked: ARC, ELLIPSE, LABEL and DOT draw around where it stands without moving it,
+    so whatever goes past the edge is cut off as in a window.
+*/
+#[derive(Debug, PartialEq)]
+pub enum EdgeMode {
+    // Keep going off the canvas, nothing out there gets drawn.
+    Window,
+    // Come back in on the opposite edge.
+    Wrap,
+    // Moving off the canvas is an error.
+    Fence,
+}
+
 impl Turtle {
     pub fn new(coords: (f32, f32)) -> Self {
         Turtle {
             coords,
+            home: coords,
+            scrunch: (1.0, 1.0),
             colour: 7,
             pen_state: PenState::Up,
+            pen_mode: PenMode::Paint,
             heading: 0,
+            fill_colour: 7,
+            fill_rule: FillRule::EvenOdd,
+            fill_path: None,
+            label_font: String::from("sans-serif"),
+            label_height: 12.0,
+            edge_mode: EdgeMode::Window,
+            saved: Vec::new(),
+            visible: false,
+            shape: Shape::Triangle,
+            solid: None,
+            gradient: None,
         }
     }
 
@@ -27,23 +129,191 @@ impl Turtle {
         self.pen_state = new_value;
     }
 
+    pub fn change_pen_mode(&mut self, new_value: PenMode) {
+        self.pen_mode = new_value;
+    }
+
+    pub fn pen_down(&self) -> bool {
+        self.pen_state == PenState::Down
+    }
+
+    pub fn pen_mode(&self) -> PenMode {
+        self.pen_mode
+    }
+
+    // What the pen leaves behind, given its mode and colour.
+    fn ink(&self) -> Ink {
+        match self.pen_mode {
+            PenMode::Paint => Ink::Paint(self.colour),
+            PenMode::Erase => Ink::Erase,
+            PenMode::Reverse => Ink::Reverse(self.colour),
+        }
+    }
+
+    pub fn change_visible(&mut self, new_value: bool) {
+        self.visible = new_value;
+    }
+
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn change_shape(&mut self, new_value: Shape) {
+        self.shape = new_value;
+    }
+
     pub fn change_colour(&mut self, new_value: usize) {
         self.colour = new_value;
+        self.gradient = None;
+    }
+
+    // Start drawing with a colour that changes as the turtle draws, picking up the first colour now.
+    pub fn change_gradient(&mut self, canvas: &mut Canvas, new_value: Gradient) {
+        self.colour = canvas.colour(new_value.colour_ahead(0.0));
+        self.gradient = Some(new_value);
+    }
+
+    pub fn change_fill_colour(&mut self, new_value: usize) {
+        self.fill_colour = new_value;
+    }
+
+    pub fn change_fill_rule(&mut self, new_value: FillRule) {
+        self.fill_rule = new_value;
+    }
+
+    pub fn change_label_font(&mut self, new_value: &str) {
+        self.label_font = new_value.to_string();
+    }
+
+    pub fn change_label_height(&mut self, new_value: f32) {
+        self.label_height = new_value;
+    }
+
+    pub fn change_edge_mode(&mut self, new_value: EdgeMode) {
+        self.edge_mode = new_value;
     }
 
     pub fn change_x(&mut self, new_value: f32) {
         self.coords.0 = new_value;
+        self.follow_coords();
+        self.record_vertex();
     }
 
     pub fn change_y(&mut self, new_value: f32) {
         self.coords.1 = new_value;
+        self.follow_coords();
+        self.record_vertex();
+    }
+
+    pub fn change_scrunch(&mut self, new_value: (f32, f32)) {
+        self.scrunch = new_value;
     }
 
     pub fn change_heading(&mut self, new_value: i32) {
-        self.heading = new_value;
+        if self.solid.is_some() {
+            self.turn_solid((new_value - self.heading) as f32);
+        } else {
+            self.heading = new_value;
+        }
+    }
+
+    // Lift the turtle off the screen into 3D, or change how it is shown if it already is.
+    pub fn change_projection(&mut self, new_value: Projection) {
+        let solid = match self.solid {
+            Some(solid) => Solid {
+                projection: new_value,
+                ..solid
+            },
+            None => {
+                let (x, y) = self.pos();
+                Solid {
+                    position: (x, y, 0.0),
+                    orientation: Orientation::flat(self.heading as f32),
+                    projection: new_value,
+                }
+            }
+        };
+        self.place(solid);
     }
 
-    pub fn draw(&mut self, image: &mut Image, mut direction: i32, mut distance: f32) {
+    // Move a 3D turtle into or out of the screen without drawing. False for a flat turtle.
+    pub fn change_z(&mut self, new_value: f32) -> bool {
+        match self.solid {
+            Some(mut solid) => {
+                solid.position.2 = new_value;
+                self.place(solid);
+                self.record_vertex();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn z(&self) -> f32 {
+        self.solid.map_or(0.0, |solid| solid.position.2)
+    }
+
+    pub fn pitch(&self) -> i32 {
+        self.solid
+            .map_or(0, |solid| solid.orientation.pitch().round() as i32)
+    }
+
+    pub fn roll(&self) -> i32 {
+        self.solid
+            .map_or(0, |solid| solid.orientation.roll().round() as i32)
+    }
+
+    // Tip a 3D turtle's nose up, or down if negative. False for a flat turtle.
+    pub fn pitch_up(&mut self, angle: i32) -> bool {
+        match self.solid {
+            Some(mut solid) => {
+                solid.orientation.pitch_up(angle as f32);
+                self.place(solid);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Roll a 3D turtle onto its right side, or its left if negative. False for a flat turtle.
+    pub fn roll_right(&mut self, angle: i32) -> bool {
+        match self.solid {
+            Some(mut solid) => {
+                solid.orientation.roll_right(angle as f32);
+                self.place(solid);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn draw(
+        &mut self,
+        canvas: &mut Canvas,
+        direction: i32,
+        distance: f32,
+    ) -> Result<(), LocError> {
+        self.move_by(canvas, direction, distance)?;
+        self.next_segment(canvas);
+        Ok(())
+    }
+
+    fn move_by(
+        &mut self,
+        canvas: &mut Canvas,
+        mut direction: i32,
+        mut distance: f32,
+    ) -> Result<(), LocError> {
+        if let Some(solid) = self.solid {
+            // Directions are relative for a 3D turtle, so work out which way that is in space.
+            let (sin, cos) = (direction as f32).to_radians().sin_cos();
+            let step = add(
+                scale(solid.orientation.forward(), cos),
+                scale(solid.orientation.right(), sin),
+            );
+            return self.draw_solid(canvas, add(solid.position, scale(step, distance)));
+        }
+
         // invert the direction if required
         direction += self.heading;
         if distance < 0.0 {
@@ -51,17 +321,666 @@ impl Turtle {
             direction += 180;
         }
         let (x, y) = self.coords;
+        let mut end = get_end_coordinates(x, y, direction, distance);
+        if self.scrunch != (1.0, 1.0) {
+            end = (
+                x + (end.0 - x) * self.scrunch.0,
+                y + (end.1 - y) * self.scrunch.1,
+            );
+        }
+        if self.edge_mode == EdgeMode::Fence && !canvas.contains(end) {
+            return Err(LocError::new(
+                "The turtle hit the fence",
+                *Location::caller(),
+            ));
+        }
+
         // draw the line and move the turtle OR just move the turtle
-        if self.pen_state == PenState::Down {
-            self.coords = image
-                .draw_simple_line(x, y, direction, distance, COLORS[self.colour])
-                .unwrap();
+        if self.edge_mode == EdgeMode::Wrap {
+            self.wrap_to(canvas, end);
+        } else if self.pen_state == PenState::Down && self.scrunch != (1.0, 1.0) {
+            // A stretched line needn't head in whole degrees, so it goes point to point.
+            self.pen_line_between(canvas, (x, y), end);
+            self.coords = end;
+        } else if self.pen_state == PenState::Down {
+            self.coords = self.pen_line(canvas, (x, y), direction, distance);
         } else {
-            self.coords = get_end_coordinates(x, y, direction, distance);
+            self.coords = end;
+        }
+        self.record_vertex();
+        Ok(())
+    }
+
+    // Move a 3D turtle in a straight line, drawing the line as it looks once projected.
+    fn draw_solid(&mut self, canvas: &mut Canvas, end: Vector) -> Result<(), LocError> {
+        let mut solid = self.solid.unwrap();
+        let flat_end = project(end, solid.projection).map(|point| self.image_space_of(point));
+        if self.edge_mode == EdgeMode::Fence && !flat_end.is_some_and(|end| canvas.contains(end)) {
+            return Err(LocError::new(
+                "The turtle hit the fence",
+                *Location::caller(),
+            ));
+        }
+
+        if self.pen_state == PenState::Down {
+            if let Some((start, stop)) = project_line(solid.position, end, solid.projection) {
+                let (start, stop) = (self.image_space_of(start), self.image_space_of(stop));
+                self.pen_line_between(canvas, start, stop);
+            }
+        }
+        solid.position = end;
+        self.place(solid);
+        self.record_vertex();
+        Ok(())
+    }
+
+    /*
+        Draws a line with the pen, which is always down when this is called. A gradient
+        that changes along the line splits it into short pieces, each its own colour.
+    */
+    fn pen_line(
+        &mut self,
+        canvas: &mut Canvas,
+        start: (f32, f32),
+        direction: i32,
+        distance: f32,
+    ) -> (f32, f32) {
+        match &self.gradient {
+            Some(gradient)
+                if self.pen_mode == PenMode::Paint
+                    && matches!(gradient.spread, Spread::Along(_)) =>
+            {
+                let end = get_end_coordinates(start.0, start.1, direction, distance);
+                self.pen_pieces(canvas, start, end, MAX_GRADIENT_PIECES);
+                end
+            }
+            _ => canvas.line(start, direction, distance, self.ink()),
+        }
+    }
+
+    fn pen_line_between(&mut self, canvas: &mut Canvas, start: (f32, f32), end: (f32, f32)) {
+        self.pen_pieces(canvas, start, end, MAX_GRADIENT_PIECES);
+    }
+
+    // The same, splitting the line into no more than most pieces.
+    fn pen_pieces(&mut self, canvas: &mut Canvas, start: (f32, f32), end: (f32, f32), most: usize) {
+        let mut gradient = match self.gradient.take() {
+            Some(gradient)
+                if self.pen_mode == PenMode::Paint
+                    && matches!(gradient.spread, Spread::Along(_)) =>
+            {
+                gradient
+            }
+            gradient => {
+                self.gradient = gradient;
+                canvas.line_between(start, end, self.ink());
+                return;
+            }
+        };
+
+        let distance = (end.0 - start.0).hypot(end.1 - start.1);
+        let pieces = (distance / GRADIENT_PIECE).ceil().clamp(1.0, most as f32);
+        let piece = distance / pieces;
+        let at = |index: usize| {
+            let t = index as f32 / pieces;
+            (
+                start.0 + (end.0 - start.0) * t,
+                start.1 + (end.1 - start.1) * t,
+            )
+        };
+        for index in 0..pieces as usize {
+            self.colour = canvas.colour(gradient.colour_ahead(piece / 2.0));
+            canvas.line_between(at(index), at(index + 1), self.ink());
+            gradient.advance(piece);
+        }
+        self.colour = canvas.colour(gradient.colour_ahead(0.0));
+        self.gradient = Some(gradient);
+    }
+
+    // Draws part of an ellipse with the pen, following any gradient along it.
+    fn pen_arc(
+        &mut self,
+        canvas: &mut Canvas,
+        centre: (f32, f32),
+        radii: (f32, f32),
+        rotation: f32,
+        start: f32,
+        sweep: f32,
+    ) {
+        if self.gradient.is_none() {
+            canvas.arc(centre, radii, rotation, start, sweep, self.ink());
+            return;
+        }
+        let points = arc_points(centre, radii, rotation, start, sweep);
+        // The whole arc gets the pieces one line could have, shared between its chords.
+        let most = (MAX_GRADIENT_PIECES / points.len()).max(1);
+        for pair in points.windows(2) {
+            self.pen_pieces(canvas, pair[0], pair[1], most);
+        }
+    }
+
+    // Once a line is drawn, a gradient that changes from line to line moves on to its next colour.
+    fn next_segment(&mut self, canvas: &mut Canvas) {
+        if self.pen_state == PenState::Up {
+            return;
+        }
+        if let Some(gradient) = self.gradient.as_mut() {
+            if let Spread::Segment(_) = gradient.spread {
+                gradient.advance(1.0);
+                self.colour = canvas.colour(gradient.colour_ahead(0.0));
+            }
+        }
+    }
+
+    // After jumping somewhere, wrap back onto the canvas or check the fence.
+    pub fn check_edges(&mut self, canvas: &Canvas) -> Result<(), LocError> {
+        match self.edge_mode {
+            // There is no wrapping a projection, so 3D turtles carry on off the canvas.
+            EdgeMode::Window => Ok(()),
+            EdgeMode::Wrap if self.solid.is_some() => Ok(()),
+            EdgeMode::Wrap => {
+                self.coords = wrapped(self.coords, canvas.wrap_area());
+                Ok(())
+            }
+            EdgeMode::Fence if !canvas.contains(self.coords) => Err(LocError::new(
+                "The turtle hit the fence",
+                *Location::caller(),
+            )),
+            EdgeMode::Fence => Ok(()),
+        }
+    }
+
+    // Head in a straight line to end, coming back in the opposite edge whenever we leave the canvas.
+    fn wrap_to(&mut self, canvas: &mut Canvas, end: (f32, f32)) {
+        let area = canvas.wrap_area();
+        let start = wrapped(self.coords, area);
+        let (dx, dy) = (end.0 - self.coords.0, end.1 - self.coords.1);
+        let mut distance = dx.hypot(dy);
+        if distance == 0.0 {
+            self.coords = start;
+            return;
+        }
+        let heading = (dx / distance, dy / distance);
+        let ahead = |distance: f32| {
+            (
+                start.0 + heading.0 * distance,
+                start.1 + heading.1 * distance,
+            )
+        };
+
+        // Past a whole period the line only goes over itself, so the rest is where it ends up.
+        let period = wrap_period(heading, area.1);
+        if distance > period {
+            if self.pen_state == PenState::Down {
+                self.wrap_line(canvas, area, start, ahead(period));
+            }
+            distance %= period;
+        }
+        self.coords = self.wrap_line(canvas, area, start, ahead(distance));
+    }
+
+    // Goes along a line from a point in the wrap area, drawing it if the pen is down, and returns where it stops.
+    fn wrap_line(
+        &mut self,
+        canvas: &mut Canvas,
+        (low, (width, height)): ((f32, f32), (f32, f32)),
+        mut start: (f32, f32),
+        mut end: (f32, f32),
+    ) -> (f32, f32) {
+        loop {
+            // How far along the line we get before crossing each edge.
+            let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+            let to_edge = |position: f32, change: f32, low: f32, size: f32| {
+                if change > 0.0 {
+                    (low + size - position) / change
+                } else if change < 0.0 {
+                    (low - position) / change
+                } else {
+                    f32::INFINITY
+                }
+            };
+            let (x_edge, y_edge) = (
+                to_edge(start.0, dx, low.0, width),
+                to_edge(start.1, dy, low.1, height),
+            );
+            let along = x_edge.min(y_edge).clamp(0.0, 1.0);
+
+            let stop = (start.0 + dx * along, start.1 + dy * along);
+            if self.pen_state == PenState::Down {
+                self.pen_line_between(canvas, start, stop);
+            }
+            if along >= 1.0 {
+                return stop;
+            }
+
+            // Jump across to the other side, along with where we are heading.
+            let shift = (
+                if x_edge <= y_edge {
+                    -dx.signum() * width
+                } else {
+                    0.0
+                },
+                if y_edge <= x_edge {
+                    -dy.signum() * height
+                } else {
+                    0.0
+                },
+            );
+            start = (stop.0 + shift.0, stop.1 + shift.1);
+            end = (end.0 + shift.0, end.1 + shift.1);
         }
     }
 
     pub fn turn(&mut self, turn: i32) {
-        self.heading += turn;
+        if self.solid.is_some() {
+            self.turn_solid(turn as f32);
+        } else {
+            self.heading += turn;
+        }
+    }
+
+    fn turn_solid(&mut self, turn: f32) {
+        if let Some(mut solid) = self.solid {
+            solid.orientation.turn(turn);
+            self.place(solid);
+        }
+    }
+
+    // Puts a 3D turtle somewhere new, keeping its coords and heading in step with how it looks.
+    fn place(&mut self, solid: Solid) {
+        if let Some(point) = project(solid.position, solid.projection) {
+            self.coords = self.image_space_of(point);
+        }
+        self.heading = solid.orientation.heading().round() as i32;
+        self.solid = Some(solid);
+    }
+
+    // After a 3D turtle is moved on the canvas, move it to match without changing its depth.
+    fn follow_coords(&mut self) {
+        if let Some(mut solid) = self.solid {
+            let (x, y) = self.turtle_space_of(self.coords);
+            solid.position = (x, y, solid.position.2);
+            self.place(solid);
+        }
+    }
+
+    pub fn push_state(&mut self) {
+        self.saved.push(SavedState {
+            coords: self.coords,
+            heading: self.heading,
+            pen_state: self.pen_state,
+            pen_mode: self.pen_mode,
+            colour: self.colour,
+            gradient: self.gradient.clone(),
+            fill_colour: self.fill_colour,
+            fill_rule: self.fill_rule,
+            label_font: self.label_font.clone(),
+            label_height: self.label_height,
+            solid: self.solid,
+        });
+    }
+
+    // Put the turtle back how it was at the last PUSHTURTLE. False if nothing was saved.
+    pub fn pop_state(&mut self) -> bool {
+        match self.saved.pop() {
+            Some(state) => {
+                self.coords = state.coords;
+                self.heading = state.heading;
+                self.pen_state = state.pen_state;
+                self.pen_mode = state.pen_mode;
+                self.colour = state.colour;
+                self.gradient = state.gradient;
+                self.fill_colour = state.fill_colour;
+                self.fill_rule = state.fill_rule;
+                self.label_font = state.label_font;
+                self.label_height = state.label_height;
+                self.solid = state.solid;
+                self.record_vertex();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Go back to where the turtle started, facing up.
+    pub fn home(&mut self) {
+        self.coords = self.home;
+        self.heading = 0;
+        if let Some(solid) = self.solid {
+            self.place(Solid {
+                position: (0.0, 0.0, 0.0),
+                orientation: Orientation::flat(0.0),
+                ..solid
+            });
+        }
+        self.record_vertex();
+    }
+
+    // Move to a position in turtle space. Like SETX and SETY this never draws.
+    pub fn set_pos(&mut self, position: (f32, f32)) {
+        self.coords = self.image_space_of(position);
+        self.follow_coords();
+        self.record_vertex();
+    }
+
+    pub fn pos(&self) -> (f32, f32) {
+        self.turtle_space_of(self.coords)
+    }
+
+    // The heading that would point the turtle at a position in turtle space.
+    pub fn towards(&self, position: (f32, f32)) -> i32 {
+        let (x, y) = self.pos();
+        let (dx, dy) = (position.0 - x, position.1 - y);
+        (dx.atan2(dy).to_degrees().round() as i32).rem_euclid(360)
+    }
+
+    // How far away a position in turtle space is, measured in turtle space.
+    pub fn distance(&self, position: (f32, f32)) -> f32 {
+        let (x, y) = self.pos();
+        ((position.0 - x).powi(2) + (position.1 - y).powi(2)).sqrt()
+    }
+
+    fn turtle_space_of(&self, point: (f32, f32)) -> (f32, f32) {
+        (
+            (point.0 - self.home.0) / self.scrunch.0,
+            (self.home.1 - point.1) / self.scrunch.1,
+        )
+    }
+
+    fn image_space_of(&self, point: (f32, f32)) -> (f32, f32) {
+        (
+            self.home.0 + point.0 * self.scrunch.0,
+            self.home.1 - point.1 * self.scrunch.1,
+        )
+    }
+
+    // Draw part of a circle centred on the turtle, starting from its heading. The turtle stays put.
+    pub fn arc(&mut self, canvas: &mut Canvas, angle: f32, radius: f32) {
+        if self.pen_state == PenState::Down {
+            let heading = self.heading as f32;
+            self.pen_arc(canvas, self.coords, (radius, radius), 0.0, heading, angle);
+            self.next_segment(canvas);
+        }
+    }
+
+    // Draw an ellipse centred on the turtle, with the y radius lying along its heading.
+    pub fn ellipse(&mut self, canvas: &mut Canvas, x_radius: f32, y_radius: f32) {
+        if self.pen_state == PenState::Down {
+            let heading = self.heading as f32;
+            self.pen_arc(
+                canvas,
+                self.coords,
+                (x_radius, y_radius),
+                heading,
+                0.0,
+                360.0,
+            );
+            self.next_segment(canvas);
+        }
+    }
+
+    // Move along a curve of the given radius while turning through the angle, right if positive.
+    pub fn arc_forward(
+        &mut self,
+        canvas: &mut Canvas,
+        angle: i32,
+        radius: f32,
+    ) -> Result<(), LocError> {
+        let radius = radius.abs();
+        if self.solid.is_some() {
+            // A 3D turtle goes round in short straight steps, staying level with its wings.
+            // Going all the way round ends where it started, so whole extra turns are left out.
+            let angle = wound(angle as f32);
+            let steps = (angle.abs() / 5.0).ceil().max(1.0);
+            let turn = angle / steps;
+            let chord = 2.0 * radius * (turn / 2.0).to_radians().sin().abs();
+            for _ in 0..steps as usize {
+                self.turn_solid(turn / 2.0);
+                self.move_by(canvas, 0, chord)?;
+                self.turn_solid(turn / 2.0);
+            }
+            self.next_segment(canvas);
+            return Ok(());
+        }
+        let side = if angle < 0 { -90 } else { 90 };
+        let centre = get_end_coordinates(self.coords.0, self.coords.1, self.heading + side, radius);
+        let start = (self.heading - side) as f32;
+
+        let points = arc_points(centre, (radius, radius), 0.0, start, angle as f32);
+        match self.edge_mode {
+            EdgeMode::Fence if points.iter().any(|point| !canvas.contains(*point)) => {
+                return Err(LocError::new(
+                    "The turtle hit the fence",
+                    *Location::caller(),
+                ));
+            }
+            // Wrapping means the curve could be split up anywhere, so follow it a piece at a time.
+            EdgeMode::Wrap => {
+                for pair in points.windows(2) {
+                    let step = (pair[1].0 - pair[0].0, pair[1].1 - pair[0].1);
+                    self.wrap_to(canvas, (self.coords.0 + step.0, self.coords.1 + step.1));
+                    self.record_vertex();
+                }
+            }
+            _ => {
+                if self.pen_state == PenState::Down {
+                    let sweep = angle as f32;
+                    self.pen_arc(canvas, centre, (radius, radius), 0.0, start, sweep);
+                }
+                if let Some(path) = self.fill_path.as_mut() {
+                    path.extend(points.iter().skip(1));
+                }
+                self.coords = *points.last().unwrap();
+            }
+        }
+        self.heading += angle;
+        self.next_segment(canvas);
+        Ok(())
+    }
+
+    // Write text at the turtle, upright when the turtle faces up. Text is drawn whatever the pen state.
+    pub fn label(&self, canvas: &mut Canvas, text: &str) {
+        let heading = self.heading as f32;
+        canvas.text(
+            self.coords,
+            text,
+            &self.label_font,
+            self.label_height,
+            heading,
+            self.ink(),
+        );
+    }
+
+    // Start recording every position the turtle visits as the outline of a polygon.
+    pub fn begin_fill(&mut self) {
+        self.fill_path = Some(vec![self.coords]);
+    }
+
+    // Stop recording and fill the polygon that was traced out since BEGINFILL.
+    pub fn end_fill(&mut self, canvas: &mut Canvas) -> bool {
+        match self.fill_path.take() {
+            Some(path) => {
+                canvas.fill_polygon(&path, self.fill_rule, self.fill_colour);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Flood fill the area the turtle is currently standing in.
+    pub fn fill(&self, canvas: &mut Canvas) {
+        canvas.flood_fill(self.coords, self.fill_colour);
+    }
+
+    // Mark a dot of the given size with the pen where the turtle stands, without moving.
+    pub fn dot(&self, canvas: &mut Canvas, size: f32) {
+        canvas.dot(self.coords, size, self.ink());
+    }
+
+    // Mark the one pixel under the turtle with the pen.
+    pub fn set_pixel(&self, canvas: &mut Canvas) {
+        canvas.plot(self.coords, self.ink());
+    }
+
+    // The colour under the turtle, which is the background colour if nothing is drawn there.
+    pub fn pixel(&self, canvas: &Canvas) -> usize {
+        canvas.pixel(self.coords).unwrap_or(canvas.background())
+    }
+
+    // Whether anything has been drawn at a position in turtle space.
+    pub fn dot_at(&self, canvas: &Canvas, position: (f32, f32)) -> bool {
+        canvas.pixel(self.image_space_of(position)).is_some()
+    }
+
+    // Draw the turtle itself in its pen colour, if it is showing.
+    pub fn draw_sprite(&self, canvas: &mut Canvas) {
+        if self.visible {
+            self.draw_shape(canvas, Ink::Paint(self.colour));
+        }
+    }
+
+    // Leave a copy of the turtle's shape on the canvas, drawn with the pen whether it is up or down.
+    pub fn stamp(&self, canvas: &mut Canvas) {
+        self.draw_shape(canvas, self.ink());
+    }
+
+    // Draw the outline of the turtle's shape where it stands, turned to match its heading.
+    fn draw_shape(&self, canvas: &mut Canvas, ink: Ink) {
+        let (sin, cos) = (self.heading as f32).to_radians().sin_cos();
+        let points: Vec<(f32, f32)> = self
+            .shape
+            .outline()
+            .iter()
+            .map(|(right, ahead)| {
+                (
+                    self.coords.0 + right * cos + ahead * sin,
+                    self.coords.1 + right * sin - ahead * cos,
+                )
+            })
+            .collect();
+        for (index, start) in points.iter().enumerate() {
+            let end = points[(index + 1) % points.len()];
+            canvas.line_between(*start, end, ink);
+        }
+    }
+
+    fn record_vertex(&mut self) {
+        if let Some(path) = self.fill_path.as_mut() {
+            path.push(self.coords);
+        }
+    }
+}
+
+// Every turtle on the canvas, and which one of them commands are being given to.
+pub struct Turtles {
+    turtles: Vec<(String, Turtle)>,
+    active: usize,
+}
+
+impl Turtles {
+    // The turtle we start with is called "0.
+    pub fn new(first: Turtle) -> Self {
+        Turtles {
+            turtles: vec![(String::from("0"), first)],
+            active: 0,
+        }
+    }
+
+    pub fn active(&self) -> &Turtle {
+        &self.turtles[self.active].1
+    }
+
+    pub fn active_mut(&mut self) -> &mut Turtle {
+        &mut self.turtles[self.active].1
+    }
+
+    pub fn active_name(&self) -> &str {
+        &self.turtles[self.active].0
+    }
+
+    // Every turtle with its name, in the order they were hatched.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Turtle)> {
+        self.turtles
+            .iter()
+            .map(|(name, turtle)| (name.as_str(), turtle))
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.turtles.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    // Hatch a new turtle at the same home as the first one. False if the name is taken.
+    pub fn add(&mut self, name: &str) -> bool {
+        if self.turtles.iter().any(|(existing, _)| existing == name) {
+            return false;
+        }
+        let turtle = Turtle::new(self.turtles[0].1.home);
+        self.turtles.push((name.to_string(), turtle));
+        true
+    }
+
+    // Send every turtle back to its home, for CLEARSCREEN.
+    pub fn home_all(&mut self) {
+        self.turtles
+            .iter_mut()
+            .for_each(|(_, turtle)| turtle.home());
+    }
+
+    // Draw every turtle that is showing, on top of everything else.
+    pub fn draw_sprites(&self, canvas: &mut Canvas) {
+        self.turtles
+            .iter()
+            .for_each(|(_, turtle)| turtle.draw_sprite(canvas));
+    }
+
+    // Send all following commands to the named turtle. False if there is no such turtle.
+    pub fn tell(&mut self, name: &str) -> bool {
+        match self
+            .turtles
+            .iter()
+            .position(|(existing, _)| existing == name)
+        {
+            Some(index) => {
+                self.active = index;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// Brings a point back into the wrap area, given as its smallest corner and size.
+fn wrapped(point: (f32, f32), (low, size): ((f32, f32), (f32, f32))) -> (f32, f32) {
+    (
+        low.0 + (point.0 - low.0).rem_euclid(size.0),
+        low.1 + (point.1 - low.1).rem_euclid(size.1),
+    )
+}
+
+/*
+    How far a line heading this way goes on a wrapping canvas before it is back where it
+    started, to within a hundredth of a step. Most headings never line up exactly, so
+    after crossing the canvas MAX_WRAPS times the line is taken to have covered it.
+*/
+fn wrap_period(heading: (f32, f32), size: (f32, f32)) -> f32 {
+    let (across, down) = (heading.0.abs(), heading.1.abs());
+    if across < 1e-6 {
+        return size.1 / down;
+    }
+    if down < 1e-6 {
+        return size.0 / across;
+    }
+    // Count the edges the line crosses most often, and see when it is back across the others.
+    let (crossing, speed, other) = if size.0 / across <= size.1 / down {
+        (size.0 / across, down, size.1)
+    } else {
+        (size.1 / down, across, size.0)
+    };
+    for times in 1..=MAX_WRAPS {
+        let others = crossing * times as f32 * speed / other;
+        if (others - others.round()).abs() * other < 0.01 {
+            return crossing * times as f32;
+        }
     }
+    crossing * MAX_WRAPS as f32
 }
@@ -0,0 +1,119 @@
+// Which way, if any, the drawing is mirrored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flip {
+    None,
+    // Mirrored left to right.
+    Horizontal,
+    // Mirrored top to bottom.
+    Vertical,
+}
+
+/*
+    Where the drawing lands on the image. Turtles always draw as if one step were one
+    pixel with the image centre in the middle; the viewport then flips, scales and turns
+    that about a centre, normally the middle of the image, and shifts it by the origin.
+    Last of all the zoom and shift from --fit move the whole picture to fit the image.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    // The point on the image everything is scaled and turned about.
+    pub centre: (f32, f32),
+    pub scale: f32,
+    // How far the drawing is moved, right and up, in image pixels.
+    pub origin: (f32, f32),
+    // Whole degrees clockwise.
+    pub rotation: i32,
+    pub flip: Flip,
+    pub zoom: f32,
+    // In image pixels, right and down.
+    pub shift: (f32, f32),
+}
+
+impl Viewport {
+    pub fn new(centre: (f32, f32)) -> Self {
+        Viewport {
+            centre,
+            scale: 1.0,
+            origin: (0.0, 0.0),
+            rotation: 0,
+            flip: Flip::None,
+            zoom: 1.0,
+            shift: (0.0, 0.0),
+        }
+    }
+
+    // Leaving the default viewport alone keeps every point exactly where it was drawn.
+    fn is_identity(&self) -> bool {
+        *self == Viewport::new(self.centre)
+    }
+
+    // Where a point lands on the image.
+    pub fn apply(&self, point: (f32, f32)) -> (f32, f32) {
+        if self.is_identity() {
+            return point;
+        }
+        let centre = self.centre;
+        let (mut x, mut y) = (point.0 - centre.0, point.1 - centre.1);
+        match self.flip {
+            Flip::None => (),
+            Flip::Horizontal => x = -x,
+            Flip::Vertical => y = -y,
+        }
+        let (sin, cos) = (self.rotation as f32).to_radians().sin_cos();
+        (x, y) = (x * self.scale, y * self.scale);
+        let (x, y) = (
+            centre.0 + self.origin.0 + x * cos - y * sin,
+            centre.1 - self.origin.1 + x * sin + y * cos,
+        );
+        (x * self.zoom + self.shift.0, y * self.zoom + self.shift.1)
+    }
+
+    // The same viewport without the zoom and shift from --fit, which is how the program sees it.
+    pub fn unfitted(&self) -> Viewport {
+        Viewport {
+            zoom: 1.0,
+            shift: (0.0, 0.0),
+            ..*self
+        }
+    }
+
+    // Where a point on the image the program was written for was drawn, leaving out any fit.
+    pub fn unapply(&self, point: (f32, f32)) -> (f32, f32) {
+        let centre = self.centre;
+        let (x, y) = (
+            point.0 - centre.0 - self.origin.0,
+            point.1 - centre.1 + self.origin.1,
+        );
+        let (sin, cos) = (self.rotation as f32).to_radians().sin_cos();
+        let (mut x, mut y) = (
+            (x * cos + y * sin) / self.scale,
+            (y * cos - x * sin) / self.scale,
+        );
+        match self.flip {
+            Flip::None => (),
+            Flip::Horizontal => x = -x,
+            Flip::Vertical => y = -y,
+        }
+        (centre.0 + x, centre.1 + y)
+    }
+
+    // How many image pixels one turtle step covers.
+    pub fn pixels_per_step(&self) -> f32 {
+        self.scale * self.zoom
+    }
+
+    // Which way an angle on the canvas, like those of arcs and text, ends up facing on the image.
+    pub fn angle(&self, angle: f32) -> f32 {
+        let flipped = match self.flip {
+            Flip::None => angle,
+            Flip::Horizontal => -angle,
+            Flip::Vertical => 180.0 - angle,
+        };
+        flipped + self.rotation as f32
+    }
+
+    // Whether things come out mirrored, which turns arcs the other way round.
+    pub fn mirrors(&self) -> bool {
+        self.flip != Flip::None
+    }
+}